@@ -0,0 +1,99 @@
+//! This module reads the configuration file of the downloader.
+//! The file is a JSON object whose keys mirror the transport flags of `download`, e.g.
+//!
+//! ```json
+//! {
+//!     "proxy": "http://proxy.example.edu:3128",
+//!     "connect_timeout_secs": 10,
+//!     "read_timeout_secs": 60,
+//!     "user_agent": "ut_grade_parser",
+//!     "ca_certificates": ["campus-root-ca.pem"]
+//! }
+//! ```
+//!
+//! Every key is optional, and flags given on the command line take precedence over the file.
+
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The settings of a configuration file.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The proxy every request goes through
+    pub proxy: Option<String>,
+    pub connect_timeout_secs: Option<f64>,
+    pub read_timeout_secs: Option<f64>,
+    pub user_agent: Option<String>,
+    /// PEM files of extra trusted root certificates, relative to the configuration file
+    #[serde(default)]
+    pub ca_certificates: Vec<PathBuf>,
+}
+
+impl Config {
+    /// Reads a configuration file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the JSON configuration file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Config)` - The settings, with certificate paths resolved against the file's directory.
+    /// * `Err(String)` - If the file can't be read or has invalid settings.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json: String = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        let mut config: Config = serde_json::from_str(&json)
+            .map_err(|err| format!("Invalid configuration file {}: {}", path.display(), err))?;
+
+        for (key, seconds) in [
+            ("connect_timeout_secs", config.connect_timeout_secs),
+            ("read_timeout_secs", config.read_timeout_secs),
+        ] {
+            if let Some(seconds) = seconds {
+                seconds_to_duration(seconds)
+                    .map_err(|err| format!("Invalid {} in {}: {}", key, path.display(), err))?;
+            }
+        }
+
+        let directory: &Path = path.parent().unwrap_or(Path::new(""));
+        config.ca_certificates = config
+            .ca_certificates
+            .iter()
+            .map(|certificate| directory.join(certificate))
+            .collect();
+
+        Ok(config)
+    }
+
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout_secs
+            .and_then(|seconds| seconds_to_duration(seconds).ok())
+    }
+
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.read_timeout_secs
+            .and_then(|seconds| seconds_to_duration(seconds).ok())
+    }
+}
+
+/// Parses a positive number of seconds, e.g. `30` or `2.5`.
+pub fn parse_seconds(value: &str) -> Result<Duration, String> {
+    let seconds: f64 = value
+        .parse()
+        .map_err(|_| format!("Invalid number of seconds {:?}", value))?;
+
+    seconds_to_duration(seconds)
+}
+
+fn seconds_to_duration(seconds: f64) -> Result<Duration, String> {
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(duration) if !duration.is_zero() => Ok(duration),
+        _ => Err(format!(
+            "Expected a positive number of seconds, got {}",
+            seconds
+        )),
+    }
+}
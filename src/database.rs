@@ -1,129 +1,147 @@
-/// This module contains functions to interact with the SQLite3 database
-/// It provides functions to create a new database, insert data into the database, and query the database.
-/// The module uses the `rusqlite` crate for interacting with the SQLite3 database.
-/// It also uses the `csv` crate for reading and writing CSV files.
-///
-/// The main functions in this module are:
-/// - `insert_data_into_db`: Inserts data from a CSV file into the database.
-/// - `insert_data_into_db_from_dir`: Inserts data from multiple CSV files in a directory into the database.
-///
-/// Example usage:
-/// ```
-/// use database::{insert_data_into_db, insert_data_into_db_from_dir};
-///
-/// // Insert data from a CSV file into the database
-/// insert_data_into_db("grade_distributions.csv");
-///
-/// // Insert data from multiple CSV files in a directory into the database
-/// insert_data_into_db_from_dir("grade_distributions");
-/// ```
+//! This module contains functions to interact with the SQLite3 database
+//! It provides functions to create a new database, insert data into the database, and query the database.
+//! The module uses the `rusqlite` crate for interacting with the SQLite3 database.
+//! It also uses the `csv` crate for reading and writing CSV files.
+//!
+//! The main functions in this module are:
+//! - `insert_data_into_db`: Inserts data from a CSV file into the database.
+//! - `insert_data_into_db_from_dir`: Inserts data from multiple CSV files in a directory into the database.
+//! - `insert_provenance_into_db`: Inserts the provenance of the CSV files listed in a manifest into the database.
+//!
+//! Example usage:
+//! ```
+//! use ut_grade_parser::database::insert_data_into_db_from_dir;
+//!
+//! let directory = std::env::temp_dir().join(format!("ut_grade_parser_doc_db_{}", std::process::id()));
+//! std::fs::create_dir_all(&directory)?;
+//! std::fs::write(
+//!     directory.join("grade_distributions_2022-2023.csv"),
+//!     "Semester\tTerm Code\tSection\tDepartment\tDepartment Code\tCourse Number\tCourse Title\tCourse Full Title\tA\tB\n\
+//!      Fall 2022\t20229\t12345\tComputer Science\tC S\t314\tDATA STRUCTURES\tC S 314 DATA STRUCTURES\t10\t5\n",
+//! )?;
+//! let database = directory.join("grade_distributions.db");
+//!
+//! // Insert data from every CSV file of a directory into a new database
+//! insert_data_into_db_from_dir(database.to_str().unwrap(), directory.to_str().unwrap())?;
+//!
+//! let connection = rusqlite::Connection::open(&database)?;
+//! let a_count: u32 = connection.query_row(
+//!     "SELECT A FROM grade_distributions_2022_2023 WHERE Term_Code = 20229",
+//!     [],
+//!     |row| row.get(0),
+//! )?;
+//! assert_eq!(a_count, 10);
+//! # std::fs::remove_dir_all(&directory)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::grade::GradeView;
+use crate::manifest::Manifest;
+
+/// The database written by the `database` command
+pub const DEFAULT_DATABASE: &str = "grade_distributions.db";
+
+/// Returns the name of the table holding the data of a CSV file.
+fn table_name(csv_file: &str) -> String {
+    // Remove the extension from the csv_file
+    std::path::Path::new(csv_file)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap()
+        .replace("-", "_")
+}
+
+/// The course columns of a parsed file and their SQL types, followed by one column per grade
+const COURSE_COLUMNS: [(&str, &str); 8] = [
+    ("Semester", "TEXT"),
+    ("Term_Code", "INTEGER"),
+    ("Section", "INTEGER"),
+    ("Department", "TEXT"),
+    ("Department_Code", "TEXT"),
+    ("Course_Number", "TEXT"),
+    ("Course_Title", "TEXT"),
+    ("Course_Full_Title", "TEXT"),
+];
 
 /// Inserts data from a CSV file into the database.
 ///
+/// The table has the course columns followed by a column per grade column of the file, so files
+/// of any grade view can be inserted.
+///
 /// # Arguments
 ///
+/// * `database` - The path to the SQLite database.
 /// * `csv_file` - The path to the CSV file.
 ///
 /// # Returns
 ///
 /// * `Result<(), Box<dyn std::error::Error>>` - A result indicating success or failure.
-pub fn insert_data_into_db(csv_file: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let db_connection: rusqlite::Connection = rusqlite::Connection::open("grade_distributions.db")?;
+pub fn insert_data_into_db(
+    database: &str,
+    csv_file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let db_connection: rusqlite::Connection = rusqlite::Connection::open(database)?;
 
-    // Remove the extension from the csv_file and store it in table_name
-    let table_name: String = std::path::Path::new(csv_file)
-        .file_stem()
-        .and_then(|stem| stem.to_str())
-        .unwrap()
-        .replace("-", "_");
+    let table_name: String = table_name(csv_file);
+
+    let mut csv_reader: csv::Reader<std::fs::File> = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(b'\t')
+        .from_path(csv_file)?;
+    let grade_columns: Vec<String> = csv_reader
+        .headers()?
+        .iter()
+        .skip(COURSE_COLUMNS.len())
+        .map(GradeView::column_name_of_label)
+        .collect::<Result<Vec<String>, _>>()?;
+    let columns: Vec<(&str, &str)> = COURSE_COLUMNS
+        .into_iter()
+        .chain(
+            grade_columns
+                .iter()
+                .map(|column_name| (column_name.as_str(), "INTEGER")),
+        )
+        .collect();
 
     // Create a new table per semester
     db_connection.execute(
         &format!(
-            r#"CREATE TABLE {} (
-                Semester TEXT,
-                Section INTEGER,
-                Department TEXT,
-                Department_Code TEXT,
-                Course_Number TEXT,
-                Course_Title TEXT,
-                Course_Full_Title TEXT,
-                A INTEGER,
-                A_Minus INTEGER,
-                B_Plus INTEGER,
-                B INTEGER,
-                B_Minus INTEGER,
-                C_Plus INTEGER,
-                C INTEGER,
-                C_Minus INTEGER,
-                D_Plus INTEGER,
-                D INTEGER,
-                D_Minus INTEGER,
-                F INTEGER,
-                Other INTEGER
-            )"#,
-            table_name
+            "CREATE TABLE {} ({})",
+            table_name,
+            columns
+                .iter()
+                .map(|(name, sql_type)| format!("\"{}\" {}", name, sql_type))
+                .collect::<Vec<String>>()
+                .join(", ")
         ),
         [],
     )?;
 
     // Read the CSV file and insert the data into the table
-    let mut csv_reader: csv::Reader<std::fs::File> = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .delimiter(b'\t')
-        .from_path(csv_file)?;
+    let insert: String = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        table_name,
+        columns
+            .iter()
+            .map(|(name, _)| format!("\"{}\"", name))
+            .collect::<Vec<String>>()
+            .join(", "),
+        (1..=columns.len())
+            .map(|i| format!("?{}", i))
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
     for result in csv_reader.records() {
         match result {
+            Ok(record) if record.len() == columns.len() => {
+                db_connection.execute(&insert, rusqlite::params_from_iter(record.iter()))?;
+            }
             Ok(record) => {
-                db_connection.execute(
-                    &format!(
-                        r#"INSERT INTO {} (
-                            Semester,
-                            Section,
-                            Department,
-                            Department_Code,
-                            Course_Number,
-                            Course_Title,
-                            Course_Full_Title,
-                            A,
-                            A_Minus,
-                            B_Plus,
-                            B,
-                            B_Minus,
-                            C_Plus,
-                            C,
-                            C_Minus,
-                            D_Plus,
-                            D,
-                            D_Minus,
-                            F,
-                            Other
-                        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)"#,
-                        table_name
-                    ),
-                    &[
-                        &record[0].to_string(),
-                        &record[1].to_string(),
-                        &record[2].to_string(),
-                        &record[3].to_string(),
-                        &record[4].to_string(),
-                        &record[5].to_string(),
-                        &record[6].to_string(),
-                        &record[7].to_string(),
-                        &record[8].to_string(),
-                        &record[9].to_string(),
-                        &record[10].to_string(),
-                        &record[11].to_string(),
-                        &record[12].to_string(),
-                        &record[13].to_string(),
-                        &record[14].to_string(),
-                        &record[15].to_string(),
-                        &record[16].to_string(),
-                        &record[17].to_string(),
-                        &record[18].to_string(),
-                        &record[19].to_string(),
-                    ],
-                )?;
+                eprintln!(
+                    "Error reading record: expected {} fields, received {}",
+                    columns.len(),
+                    record.len()
+                );
             }
             Err(err) => {
                 eprintln!("Error reading record: {}", err);
@@ -138,18 +156,22 @@ pub fn insert_data_into_db(csv_file: &str) -> Result<(), Box<dyn std::error::Err
 ///
 /// # Arguments
 ///
+/// * `database` - The path to the SQLite database, which is replaced if it already exists.
 /// * `input_dir` - The path to the input directory containing the CSV files.
 ///
 /// # Returns
 ///
 /// * `Result<(), Box<dyn std::error::Error>>` - A result indicating success or failure.
-pub fn insert_data_into_db_from_dir(input_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub fn insert_data_into_db_from_dir(
+    database: &str,
+    input_dir: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
     // Create a new sqlite3 database
     // If the database already exists, delete it
-    if std::path::Path::new("grade_distributions.db").exists() {
-        std::fs::remove_file("grade_distributions.db")?;
+    if std::path::Path::new(database).exists() {
+        std::fs::remove_file(database)?;
     }
-    std::fs::File::create("grade_distributions.db")?;
+    std::fs::File::create(database)?;
 
     for entry in std::fs::read_dir(input_dir)? {
         let entry: std::fs::DirEntry = entry?;
@@ -159,9 +181,88 @@ pub fn insert_data_into_db_from_dir(input_dir: &str) -> Result<(), Box<dyn std::
         // TODO: Use a more robust method to check if the file is a CSV file
         if path_str.ends_with(".csv") {
             println!("Inserting data into database from: {}", path_str);
-            insert_data_into_db(path_str)?;
+            insert_data_into_db(database, path_str)?;
         }
     }
 
+    match Manifest::load(input_dir)? {
+        Some(manifest) => insert_provenance_into_db(database, &manifest)?,
+        None => eprintln!("No manifest in {}, skipping provenance", input_dir),
+    }
+
+    Ok(())
+}
+
+/// Inserts the provenance of the CSV files listed in a manifest into the `provenance` table.
+///
+/// # Arguments
+///
+/// * `database` - The path to the SQLite database.
+/// * `manifest` - The manifest of the CSV files inserted into the database.
+///
+/// # Returns
+///
+/// * `Result<(), Box<dyn std::error::Error>>` - A result indicating success or failure.
+pub fn insert_provenance_into_db(
+    database: &str,
+    manifest: &Manifest,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let db_connection: rusqlite::Connection = rusqlite::Connection::open(database)?;
+
+    db_connection.execute(
+        r#"CREATE TABLE provenance (
+            Table_Name TEXT PRIMARY KEY,
+            File_Name TEXT,
+            Academic_Year TEXT,
+            Filters TEXT,
+            Parameters TEXT,
+            Fetched_At TEXT,
+            Sha256 TEXT,
+            Rows INTEGER,
+            Source_File TEXT,
+            Source_Sha256 TEXT,
+            Source_Rows INTEGER,
+            Source_Url TEXT,
+            Tool_Version TEXT
+        )"#,
+        [],
+    )?;
+
+    for entry in &manifest.files {
+        let source = entry.derived_from.as_deref().unwrap_or(entry);
+        db_connection.execute(
+            r#"INSERT INTO provenance (
+                Table_Name,
+                File_Name,
+                Academic_Year,
+                Filters,
+                Parameters,
+                Fetched_At,
+                Sha256,
+                Rows,
+                Source_File,
+                Source_Sha256,
+                Source_Rows,
+                Source_Url,
+                Tool_Version
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)"#,
+            rusqlite::params![
+                table_name(&entry.file_name),
+                entry.file_name,
+                entry.academic_year,
+                serde_json::to_string(&entry.filters)?,
+                serde_json::to_string(&entry.parameters)?,
+                entry.fetched_at,
+                entry.sha256,
+                entry.rows,
+                source.file_name,
+                source.sha256,
+                source.rows,
+                manifest.source,
+                entry.tool_version,
+            ],
+        )?;
+    }
+
     Ok(())
 }
@@ -0,0 +1,356 @@
+//! This module defines the letter grades of the grade distributions and the counts of each grade.
+//!
+//! The main types in this module are:
+//! - `Grade`: A letter grade, parsed from the labels of the dashboard exports.
+//! - `GradeView`: How the dashboard groups grades, which decides the grade columns of a file.
+//! - `GradeDistribution`: The number of students who received each grade.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::{Add, AddAssign, Index};
+use std::str::FromStr;
+
+/// A letter grade of the grade distributions, in the order of the grade columns.
+///
+/// UT doesn't give `A+` grades, but the exports have some, which count as `A`. Grades like
+/// `CR`, `NC`, `Q` or `W` are grouped as `Other` by the dashboard.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Grade {
+    A,
+    AMinus,
+    BPlus,
+    B,
+    BMinus,
+    CPlus,
+    C,
+    CMinus,
+    DPlus,
+    D,
+    DMinus,
+    F,
+    Other,
+}
+
+impl Grade {
+    /// The number of grades
+    pub const COUNT: usize = 13;
+
+    /// Every grade, in column order
+    pub const ALL: [Grade; Grade::COUNT] = [
+        Grade::A,
+        Grade::AMinus,
+        Grade::BPlus,
+        Grade::B,
+        Grade::BMinus,
+        Grade::CPlus,
+        Grade::C,
+        Grade::CMinus,
+        Grade::DPlus,
+        Grade::D,
+        Grade::DMinus,
+        Grade::F,
+        Grade::Other,
+    ];
+
+    /// The grades of the condensed grade view, which has no plus or minus grades, in column order.
+    ///
+    /// In that view `A` counts every `A` grade, `A-` included, so its columns are labeled by
+    /// `GradeView::label` rather than `Grade::label`.
+    pub const CONDENSED: [Grade; 6] = [
+        Grade::A,
+        Grade::B,
+        Grade::C,
+        Grade::D,
+        Grade::F,
+        Grade::Other,
+    ];
+
+    /// The label of the grade in the exports and parsed files, e.g. `A-`.
+    pub fn label(self) -> &'static str {
+        match self {
+            Grade::A => "A",
+            Grade::AMinus => "A-",
+            Grade::BPlus => "B+",
+            Grade::B => "B",
+            Grade::BMinus => "B-",
+            Grade::CPlus => "C+",
+            Grade::C => "C",
+            Grade::CMinus => "C-",
+            Grade::DPlus => "D+",
+            Grade::D => "D",
+            Grade::DMinus => "D-",
+            Grade::F => "F",
+            Grade::Other => "Other",
+        }
+    }
+
+    /// The name of the database column holding the count of the grade, e.g. `A_Minus`.
+    pub fn column_name(self) -> &'static str {
+        match self {
+            Grade::AMinus => "A_Minus",
+            Grade::BPlus => "B_Plus",
+            Grade::BMinus => "B_Minus",
+            Grade::CPlus => "C_Plus",
+            Grade::CMinus => "C_Minus",
+            Grade::DPlus => "D_Plus",
+            Grade::DMinus => "D_Minus",
+            grade => grade.label(),
+        }
+    }
+
+    /// Whether the grade is a plus or minus grade, which only the expanded grade view has.
+    pub fn is_plus_or_minus(self) -> bool {
+        self.label().ends_with(['+', '-'])
+    }
+
+    /// The position of the grade in `Grade::ALL`.
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.label())
+    }
+}
+
+/// A grade label that isn't one of the grades of `Grade`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownGrade(pub String);
+
+impl fmt::Display for UnknownGrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown grade {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownGrade {}
+
+impl FromStr for Grade {
+    type Err = UnknownGrade;
+
+    /// Parses a grade label of the exports, e.g. `A-`, counting `A+` as `A`.
+    fn from_str(label: &str) -> Result<Self, Self::Err> {
+        let label: &str = label.trim();
+        if label == "A+" {
+            return Ok(Grade::A);
+        }
+
+        Grade::ALL
+            .into_iter()
+            .find(|grade| grade.label().eq_ignore_ascii_case(label))
+            .ok_or_else(|| UnknownGrade(label.to_string()))
+    }
+}
+
+/// How the dashboard groups grades, set by its `[Parameters].[Parameter 1]` parameter.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum GradeView {
+    /// Every letter grade, e.g. `A`, `A-` and `B+`
+    #[default]
+    Expanded,
+    /// Grades grouped by letter, e.g. `A-` counts as `A`
+    Condensed,
+}
+
+impl GradeView {
+    /// The dashboard parameter whose value is the grade view, as listed in download manifests
+    pub const PARAMETER: &'static str = "[Parameters].[Parameter 1]";
+
+    /// The grades of the view, in column order.
+    pub fn columns(self) -> &'static [Grade] {
+        match self {
+            GradeView::Expanded => &Grade::ALL,
+            GradeView::Condensed => &Grade::CONDENSED,
+        }
+    }
+
+    /// The label of the column of a grade in parsed files.
+    ///
+    /// The letter groups of the condensed view are labeled like `A (all)`, so that they can't be
+    /// mistaken for the `A` column of the expanded view, which excludes `A-`.
+    pub fn label(self, grade: Grade) -> String {
+        match (self, grade) {
+            (GradeView::Condensed, Grade::A | Grade::B | Grade::C | Grade::D) => {
+                format!("{} (all)", grade.label())
+            }
+            _ => grade.label().to_string(),
+        }
+    }
+
+    /// The name of the database column of a grade, e.g. `A_Minus`, or `A_All` for the `A (all)`
+    /// column of the condensed view.
+    pub fn column_name(self, grade: Grade) -> String {
+        match (self, grade) {
+            (GradeView::Condensed, Grade::A | Grade::B | Grade::C | Grade::D) => {
+                format!("{}_All", grade.label())
+            }
+            _ => grade.column_name().to_string(),
+        }
+    }
+
+    /// Returns the database column name of a column label of a parsed file, e.g. `A_All` for
+    /// `A (all)`.
+    pub fn column_name_of_label(label: &str) -> Result<String, UnknownGrade> {
+        match label.trim().strip_suffix(" (all)") {
+            Some(letter) => Ok(GradeView::Condensed.column_name(letter.parse()?)),
+            None => Ok(GradeView::Expanded.column_name(label.parse()?)),
+        }
+    }
+}
+
+impl fmt::Display for GradeView {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GradeView::Expanded => write!(f, "Expanded"),
+            GradeView::Condensed => write!(f, "Condensed"),
+        }
+    }
+}
+
+/// A value of `[Parameters].[Parameter 1]` that isn't one of the grade views of `GradeView`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownGradeView(pub String);
+
+impl fmt::Display for UnknownGradeView {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unknown grade view {:?}, expected \"Expanded\" or \"Condensed\"",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownGradeView {}
+
+impl FromStr for GradeView {
+    type Err = UnknownGradeView;
+
+    /// Parses a value of `[Parameters].[Parameter 1]`, e.g. `Expanded`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        [GradeView::Expanded, GradeView::Condensed]
+            .into_iter()
+            .find(|view| view.to_string().eq_ignore_ascii_case(value.trim()))
+            .ok_or_else(|| UnknownGradeView(value.to_string()))
+    }
+}
+
+/// The number of students who received each grade.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GradeDistribution {
+    /// The count of each grade, in `Grade::ALL` order
+    counts: [u32; Grade::COUNT],
+}
+
+impl GradeDistribution {
+    /// Adds `count` students who received `grade`.
+    pub fn record(&mut self, grade: Grade, count: u32) {
+        self.counts[grade.index()] += count;
+    }
+
+    /// Adds the counts of another distribution, e.g. of another section of the same course.
+    pub fn merge(&mut self, other: &GradeDistribution) {
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts) {
+            *count += other_count;
+        }
+    }
+
+    /// The number of students who received any grade.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().map(|&count| u64::from(count)).sum()
+    }
+
+    /// The share of the students who received each grade, in `Grade::ALL` order, or all zeros if
+    /// the distribution is empty.
+    pub fn proportions(&self) -> [f64; Grade::COUNT] {
+        let total: u64 = self.total();
+        if total == 0 {
+            return [0.0; Grade::COUNT];
+        }
+
+        self.counts.map(|count| count as f64 / total as f64)
+    }
+}
+
+impl Index<Grade> for GradeDistribution {
+    type Output = u32;
+
+    fn index(&self, grade: Grade) -> &u32 {
+        &self.counts[grade.index()]
+    }
+}
+
+impl AddAssign<&GradeDistribution> for GradeDistribution {
+    fn add_assign(&mut self, other: &GradeDistribution) {
+        self.merge(other);
+    }
+}
+
+impl Add for GradeDistribution {
+    type Output = GradeDistribution;
+
+    fn add(mut self, other: GradeDistribution) -> GradeDistribution {
+        self.merge(&other);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_grade_labels() {
+        for grade in Grade::ALL {
+            assert_eq!(grade.label().parse(), Ok(grade));
+        }
+        assert_eq!("A+".parse(), Ok(Grade::A));
+        assert_eq!(" b- ".parse(), Ok(Grade::BMinus));
+        assert_eq!("other".parse(), Ok(Grade::Other));
+        assert_eq!("E".parse::<Grade>(), Err(UnknownGrade("E".to_string())));
+        assert_eq!(Grade::CPlus.column_name(), "C_Plus");
+    }
+
+    #[test]
+    fn labels_the_columns_of_each_grade_view() {
+        assert_eq!(GradeView::Expanded.label(Grade::A), "A");
+        assert_eq!(GradeView::Condensed.label(Grade::A), "A (all)");
+        assert_eq!(GradeView::Condensed.label(Grade::Other), "Other");
+        assert_eq!(GradeView::Condensed.column_name(Grade::B), "B_All");
+        for view in [GradeView::Expanded, GradeView::Condensed] {
+            for &grade in view.columns() {
+                assert_eq!(
+                    GradeView::column_name_of_label(&view.label(grade)),
+                    Ok(view.column_name(grade))
+                );
+            }
+            assert_eq!(view.to_string().parse(), Ok(view));
+        }
+        assert_eq!(
+            "Summary".parse::<GradeView>(),
+            Err(UnknownGradeView("Summary".to_string()))
+        );
+    }
+
+    #[test]
+    fn sums_distributions() {
+        let mut fall: GradeDistribution = GradeDistribution::default();
+        fall.record(Grade::A, 3);
+        fall.record(Grade::B, 1);
+        let mut spring: GradeDistribution = GradeDistribution::default();
+        spring.record(Grade::A, 4);
+
+        let year: GradeDistribution = fall + spring;
+        assert_eq!(year[Grade::A], 7);
+        assert_eq!(year[Grade::B], 1);
+        assert_eq!(year.total(), 8);
+        assert_eq!(year.proportions()[Grade::A as usize], 0.875);
+        assert_eq!(
+            GradeDistribution::default().proportions(),
+            [0.0; Grade::COUNT]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! Downloads, parses and stores the grade distributions of the University of Texas at Austin.
+//!
+//! The grade distributions are published on a Tableau dashboard. This crate:
+//! - exports them as one CSV file per academic year with `network::fetch_and_download_grade_distributions`,
+//! - parses the exports into one row per section with a column per grade with `parse::parse_csv_directory`,
+//! - writes the parsed files to a SQLite database with `database::insert_data_into_db_from_dir`,
+//!
+//! and records the provenance of every file in a `manifest.json` (see `manifest`).
+//!
+//! The data model types, `Grade`, `GradeDistribution` and `Term`, are re-exported at the root.
+//!
+//! # Features
+//!
+//! Every feature but `native-tls` is enabled by default. Without any, the crate only parses
+//! exported CSV files and reads and writes their manifests, with `csv`, `serde`, `serde_json` and
+//! `sha2` for checksums.
+//! - `network`: The `network` and `config` modules, which download the exports with `reqwest` and
+//!   `tokio`. It needs one of the TLS backends below.
+//! - `rustls`: TLS with `rustls` and the Mozilla root certificates, the default backend.
+//! - `native-tls`: TLS with the platform's library, e.g. OpenSSL, and its root certificates. Opt
+//!   in with `default-features = false` and the other features you need.
+//! - `progress`: Progress bars while downloading, with `indicatif`.
+//! - `sqlite`: The `database` module, which writes parsed files with `rusqlite`.
+//! - `bundled-sqlite`: Builds SQLite from source instead of linking the system library.
+//! - `cli`: The `ut_grade_parser` binary, with `clap`, which also needs `network` and `sqlite`.
+//!
+//! Example usage:
+//! ```
+//! use ut_grade_parser::{Grade, GradeDistribution, Term};
+//!
+//! let term: Term = "Fall 2022".parse()?;
+//! assert_eq!(term.code(), 20229);
+//! assert_eq!(term.academic_year(), 2022);
+//!
+//! let mut distribution: GradeDistribution = GradeDistribution::default();
+//! distribution.record("A+".parse()?, 3);
+//! distribution.record(Grade::B, 1);
+//! assert_eq!(distribution[Grade::A], 3);
+//! assert_eq!(distribution.total(), 4);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+#[cfg(all(
+    feature = "network",
+    not(any(feature = "rustls", feature = "native-tls"))
+))]
+compile_error!("The `network` feature needs a TLS backend: enable `rustls` or `native-tls`");
+
+#[cfg(feature = "network")]
+pub mod config;
+#[cfg(feature = "sqlite")]
+pub mod database;
+pub mod grade;
+pub mod manifest;
+#[cfg(feature = "network")]
+pub mod network;
+pub mod parse;
+pub mod term;
+
+pub use grade::{Grade, GradeDistribution, GradeView, UnknownGrade, UnknownGradeView};
+pub use term::{InvalidTerm, Season, Term};
@@ -1,29 +1,42 @@
-mod database;
-mod network;
-mod parse;
+use ut_grade_parser::config::{parse_seconds, Config};
+use ut_grade_parser::database::{insert_data_into_db_from_dir, DEFAULT_DATABASE};
+use ut_grade_parser::network::{
+    fetch_and_download_grade_distributions, fetch_dashboard_filters, parse_academic_year,
+    parse_field_filter, AcademicYearRange, DashboardFilter, DownloadOptions, FieldFilter,
+    NetworkError, Partition, RateLimiter, RetryPolicy, SessionConfig, Tracer, Traffic, TrafficMode,
+    TransportConfig, BAR_GRAPH_SHEET, DEFAULT_BASE_URL, DEFAULT_GRADE_VIEW, SHEET_ID,
+};
+use ut_grade_parser::parse::{parse_csv_directory, Rollup};
+use ut_grade_parser::GradeView;
 
-use crate::database::insert_data_into_db_from_dir;
-use crate::network::fetch_and_download_grade_distributions;
-use crate::parse::parse_csv_directory;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 
 #[derive(Subcommand)]
 enum Commands {
     /// Fetch and download grade distributions
-    Download {
-        // /// The semester range to download grade distributions for
-        // #[clap(short, long)]
-        // years: Vec<u16>,
-    },
+    Download(Box<DownloadArgs>),
+    /// List the values of every filter of the dashboard
+    List(Box<ListArgs>),
     /// Parse CSV files
     Parse {
-        // /// The input directory containing CSV files
-        // #[clap(short, long)]
-        // input: std::path::PathBuf,
-        // /// The output directory to write parsed CSV files
-        // #[clap(short, long)]
-        // output: std::path::PathBuf,
+        /// The directory of the downloaded CSV files
+        #[arg(short, long, value_name = "DIR", default_value = "out")]
+        input: String,
+        /// The directory to write the parsed CSV files to
+        #[arg(short, long, value_name = "DIR", default_value = "out_parsed")]
+        output: String,
+        /// The grade view of every file, instead of the one listed in the manifest of the
+        /// downloaded files [default: expanded]
+        #[arg(long, value_enum)]
+        grade_view: Option<GradeView>,
+        /// Sum the sections of each course per semester or per file, instead of one row per section
+        #[arg(long, value_enum)]
+        rollup: Option<Rollup>,
     },
     /// Create a sqlite3 database
     Database,
@@ -31,54 +44,403 @@ enum Commands {
     All,
 }
 
+#[derive(Args)]
+struct DownloadArgs {
+    /// The first academic year to download (e.g. 2019 or 2019-2020)
+    #[arg(long, value_parser = parse_academic_year)]
+    from: Option<u16>,
+    /// The last academic year to download (e.g. 2022 or 2022-2023)
+    #[arg(long, value_parser = parse_academic_year)]
+    to: Option<u16>,
+    /// Only download the latest available academic year
+    #[arg(long, conflicts_with_all = ["from", "to"])]
+    latest: bool,
+    /// Only download this department prefix (e.g. "C S"), can be repeated
+    #[arg(long = "department", value_name = "PREFIX")]
+    departments: Vec<String>,
+    /// Only download rows where a dashboard filter has one of the given values, can be repeated
+    #[arg(long = "filter", value_name = "FIELD=VALUE[,VALUE...]", value_parser = parse_field_filter)]
+    filters: Vec<FieldFilter>,
+    /// How to split the downloads into files
+    #[arg(long, value_enum, default_value_t = Partition::Year)]
+    partition: Partition,
+    /// The number of sessions downloading concurrently
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: u16,
+    /// Skip academic years that a previous run already downloaded completely
+    #[arg(long)]
+    resume: bool,
+    /// The sheet to export, as named in the export crosstab dialog
+    #[arg(long, value_name = "NAME", default_value_t = SHEET_ID.to_string())]
+    sheet: String,
+    /// Also export the bar graph into bar_graph/ and fail if its grade totals don't match
+    #[arg(long, long_help = format!(
+        "Also export the \"{}\" sheet of each file into bar_graph/ and fail if its per-grade \
+         totals don't match the exported sheet, which reveals incomplete or truncated exports",
+        BAR_GRAPH_SHEET
+    ))]
+    cross_check: bool,
+    /// How the dashboard groups grades, e.g. "Expanded" for every letter grade or a condensed view
+    #[arg(long, value_name = "VIEW", default_value_t = DEFAULT_GRADE_VIEW.to_string())]
+    grade_view: String,
+    /// Don't re-export a file split by department or semester when its export fails or is short
+    #[arg(long)]
+    no_split: bool,
+    /// Re-export a file split by department or semester if it has fewer rows than this
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    min_rows: u64,
+    #[command(flatten)]
+    session: SessionArgs,
+}
+
+impl DownloadArgs {
+    fn options(&self) -> Result<DownloadOptions, NetworkError> {
+        let mut session: SessionConfig = self.session.config()?;
+        session.grade_view = self.grade_view.clone();
+
+        Ok(DownloadOptions {
+            years: AcademicYearRange {
+                from: self.from,
+                to: self.to,
+                latest: self.latest,
+            },
+            departments: self.departments.clone(),
+            partition: self.partition,
+            filters: self.filters.clone(),
+            session,
+            resume: self.resume,
+            jobs: self.jobs.into(),
+            sheet: self.sheet.clone(),
+            cross_check: self.cross_check,
+            split_exports: !self.no_split,
+            min_rows: self.min_rows,
+            ..DownloadOptions::default()
+        })
+    }
+}
+
+#[derive(Args)]
+struct ListArgs {
+    /// How to print the filter domains
+    #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+    format: ListFormat,
+    #[command(flatten)]
+    session: SessionArgs,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ListFormat {
+    /// One column per filter, one row per value
+    Table,
+    /// A JSON array of filters and their values
+    Json,
+}
+
+/// How to reach the dashboard, shared by the commands that open sessions.
+#[derive(Args)]
+struct SessionArgs {
+    /// The URL of the Tableau server hosting the dashboard
+    #[arg(long, default_value = DEFAULT_BASE_URL)]
+    base_url: String,
+    /// How many times to retry a failed request
+    #[arg(long, default_value_t = 3)]
+    retries: u32,
+    /// The delay before the first retry in milliseconds, doubled on every further retry
+    #[arg(long, default_value_t = 500)]
+    backoff_ms: u64,
+    /// The maximum number of requests per second across all sessions, at least 0.01 (0 for no
+    /// limit)
+    #[arg(long, value_name = "RATE", default_value_t = 5.0, value_parser = parse_requests_per_second)]
+    max_requests_per_second: f64,
+    /// Save every request/response pair to a directory, with session ids redacted
+    #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    /// Serve responses saved by --record instead of contacting the server
+    #[arg(long, value_name = "DIR")]
+    replay: Option<PathBuf>,
+    /// Don't check that the dashboard still has the sheets, filters and parameters used
+    #[arg(long)]
+    skip_preflight: bool,
+    /// Where to write the raw dashboard responses when the preflight check fails
+    #[arg(long, value_name = "DIR", default_value = "diagnostics")]
+    diagnostics: PathBuf,
+    /// Save the session (the first one with --jobs) to this file and reuse it in later runs while
+    /// the server keeps it alive
+    #[arg(long, value_name = "FILE")]
+    session_cache: Option<PathBuf>,
+    /// Read transport settings from a JSON file, overridden by the flags below
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Send every request through this HTTP(S) proxy (e.g. http://proxy.example.edu:3128)
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+    /// The longest wait for a connection to the server, in seconds [default: 10]
+    #[arg(long, value_name = "SECS", value_parser = parse_seconds)]
+    connect_timeout: Option<Duration>,
+    /// The longest wait for a response or the next part of a download, in seconds [default: 60]
+    #[arg(long, value_name = "SECS", value_parser = parse_seconds)]
+    read_timeout: Option<Duration>,
+    /// The User-Agent header of every request
+    #[arg(long)]
+    user_agent: Option<String>,
+    /// Also trust the root certificates of this PEM file, can be repeated
+    #[arg(long = "ca-cert", value_name = "PEM")]
+    ca_certificates: Vec<PathBuf>,
+}
+
+impl SessionArgs {
+    /// Combines the transport flags with the configuration file, the flags taking precedence.
+    fn transport(&self) -> Result<TransportConfig, NetworkError> {
+        let config: Config = match &self.config {
+            Some(path) => Config::load(path).map_err(NetworkError::Config)?,
+            None => Config::default(),
+        };
+
+        let mut transport: TransportConfig = TransportConfig::default();
+        if let Some(proxy) = self.proxy.as_ref().or(config.proxy.as_ref()) {
+            transport.set_proxy(proxy)?;
+        }
+        if let Some(connect_timeout) = self.connect_timeout.or(config.connect_timeout()) {
+            transport.connect_timeout = connect_timeout;
+        }
+        if let Some(read_timeout) = self.read_timeout.or(config.read_timeout()) {
+            transport.read_timeout = read_timeout;
+        }
+        if let Some(user_agent) = self.user_agent.as_ref().or(config.user_agent.as_ref()) {
+            transport.user_agent = user_agent.clone();
+        }
+        for path in config.ca_certificates.iter().chain(&self.ca_certificates) {
+            transport.add_ca_certificates(path)?;
+        }
+
+        Ok(transport)
+    }
+
+    fn config(&self) -> Result<SessionConfig, NetworkError> {
+        let traffic_mode: Option<TrafficMode> = match (&self.record, &self.replay) {
+            (Some(directory), _) => Some(TrafficMode::Record(directory.clone())),
+            (_, Some(directory)) => Some(TrafficMode::Replay(directory.clone())),
+            _ => None,
+        };
+        let traffic: Option<Arc<Traffic>> = traffic_mode
+            .map(|mode| Traffic::open(&mode).map(Arc::new))
+            .transpose()?;
+
+        Ok(SessionConfig {
+            base_url: self.base_url.clone(),
+            retry: RetryPolicy {
+                max_retries: self.retries,
+                initial_backoff: Duration::from_millis(self.backoff_ms),
+                ..RetryPolicy::default()
+            },
+            transport: self.transport()?,
+            traffic,
+            rate_limiter: (self.max_requests_per_second > 0.0)
+                .then(|| RateLimiter::new(self.max_requests_per_second).map(Arc::new))
+                .transpose()?,
+            preflight: !self.skip_preflight,
+            diagnostics_directory: self.diagnostics.clone(),
+            session_cache: self.session_cache.clone(),
+            ..SessionConfig::default()
+        })
+    }
+}
+
+/// The slowest request rate accepted, one request every 100 seconds
+const MIN_REQUESTS_PER_SECOND: f64 = 0.01;
+
+/// Parses a `--max-requests-per-second` value, either 0 or at least `MIN_REQUESTS_PER_SECOND`.
+fn parse_requests_per_second(value: &str) -> Result<f64, String> {
+    let requests_per_second: f64 = value
+        .parse()
+        .map_err(|_| format!("Invalid number of requests per second {:?}", value))?;
+
+    if requests_per_second == 0.0
+        || (requests_per_second.is_finite() && requests_per_second >= MIN_REQUESTS_PER_SECOND)
+    {
+        Ok(requests_per_second)
+    } else {
+        Err(format!(
+            "Expected 0 or a number of requests per second of at least {}, got {}",
+            MIN_REQUESTS_PER_SECOND, value
+        ))
+    }
+}
+
 #[derive(Parser)]
 #[command(version, about)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
 
-    /// Turn debugging information on
-    #[arg(short, long, action = clap::ArgAction::Count)]
+    /// Log every request with its status and timing (-d), and also write every exchange to the
+    /// HAR file (-dd)
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
     debug: u8,
+
+    /// Where -dd writes the HAR file, which browser devtools can open
+    #[arg(
+        long,
+        value_name = "FILE",
+        default_value = "session.har",
+        global = true
+    )]
+    har: PathBuf,
 }
 
-async fn download() -> Result<(), Box<dyn std::error::Error>> {
+impl Cli {
+    /// The tracer of the HTTP traffic requested by `--debug`, if any.
+    fn tracer(&self) -> Option<Arc<Tracer>> {
+        match self.debug {
+            0 => None,
+            1 => Some(Arc::new(Tracer::new(None))),
+            _ => Some(Arc::new(Tracer::new(Some(self.har.clone())))),
+        }
+    }
+}
+
+async fn download(options: DownloadOptions) -> Result<(), Box<dyn std::error::Error>> {
     println!("fetch_and_download_grade_distributions()");
-    fetch_and_download_grade_distributions().await?;
+    fetch_and_download_grade_distributions(&options).await?;
 
     Ok(())
 }
 
-fn parse() {
+async fn list(
+    args: &ListArgs,
+    tracer: Option<Arc<Tracer>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config: SessionConfig = args.session.config()?;
+    config.tracer = tracer;
+    let filters: Vec<DashboardFilter> = fetch_dashboard_filters(&config).await?;
+    match args.format {
+        ListFormat::Table => print!("{}", filters_table(&filters)),
+        ListFormat::Json => println!("{}", serde_json::to_string_pretty(&filters)?),
+    }
+
+    Ok(())
+}
+
+/// Formats filter domains as a table with one column per filter and one row per filter index.
+fn filters_table(filters: &[DashboardFilter]) -> String {
+    let header: Vec<&str> = std::iter::once("#")
+        .chain(filters.iter().map(|filter| filter.field.as_str()))
+        .collect();
+    let row_count: usize = filters
+        .iter()
+        .map(|filter| filter.domain.len())
+        .max()
+        .unwrap_or(0);
+    let rows: Vec<Vec<String>> = (0..row_count)
+        .map(|index| {
+            std::iter::once(index.to_string())
+                .chain(
+                    filters
+                        .iter()
+                        .map(|filter| filter.domain.get(index).cloned().unwrap_or_default()),
+                )
+                .collect()
+        })
+        .collect();
+
+    let widths: Vec<usize> = (0..header.len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .chain(std::iter::once(header[column].chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let format_row = |cells: Vec<&str>| -> String {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        format!("{}\n", line.join("  ").trim_end())
+    };
+
+    let mut table: String = format_row(header);
+    for row in &rows {
+        table.push_str(&format_row(row.iter().map(String::as_str).collect()));
+    }
+
+    table
+}
+
+fn parse(
+    input: &str,
+    output: &str,
+    grade_view: Option<GradeView>,
+    rollup: Option<Rollup>,
+) -> Result<(), Box<dyn std::error::Error>> {
     println!("parse_csv_directory()");
-    parse_csv_directory("out", "out_parsed");
+    parse_csv_directory(input, output, grade_view, rollup)?;
+
+    Ok(())
 }
 
 fn database() -> Result<(), Box<dyn std::error::Error>> {
     println!("insert_data_into_db_from_dir()");
-    insert_data_into_db_from_dir("out_parsed")?;
+    insert_data_into_db_from_dir(DEFAULT_DATABASE, "out_parsed")?;
 
     Ok(())
 }
 
-async fn all() -> Result<(), Box<dyn std::error::Error>> {
-    download().await?;
-    parse();
+async fn all(tracer: Option<Arc<Tracer>>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut options: DownloadOptions = DownloadOptions::default();
+    options.session.tracer = tracer;
+    download(options).await?;
+    parse("out", "out_parsed", None, None)?;
     database()?;
 
     Ok(())
 }
 
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let tracer: Option<Arc<Tracer>> = cli.tracer();
+    let result: Result<(), Box<dyn std::error::Error>> = match cli.command {
+        Commands::Download(args) => {
+            let mut options: DownloadOptions = args.options()?;
+            options.session.tracer = tracer.clone();
+            download(options).await
+        }
+        Commands::List(args) => list(&args, tracer.clone()).await,
+        Commands::Parse {
+            input,
+            output,
+            grade_view,
+            rollup,
+        } => parse(&input, &output, grade_view, rollup),
+        Commands::Database => database(),
+        Commands::All => all(tracer.clone()).await,
+    };
+
+    // Write the HAR file even if the command failed, since that's when it's most useful
+    if let Some(tracer) = tracer {
+        match tracer.save_har() {
+            Ok(Some(path)) => eprintln!("Wrote the HTTP traffic to {}", path.display()),
+            Ok(None) => {}
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+
+    result
+}
+
 #[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> ExitCode {
     let cli = Cli::parse();
 
-    match cli.command {
-        Commands::Download {} => download().await?,
-        Commands::Parse {} => parse(),
-        Commands::Database => database()?,
-        Commands::All => all().await?,
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            match err.downcast_ref::<NetworkError>() {
+                Some(network_error) => ExitCode::from(network_error.exit_code()),
+                None => ExitCode::FAILURE,
+            }
+        }
     }
-
-    Ok(())
 }
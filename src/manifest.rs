@@ -0,0 +1,206 @@
+//! This module describes the provenance of exported and parsed grade distribution files.
+//! A `manifest.json` written next to the files lists, for each file, where and when its data was
+//! fetched, with which filters, and its size, SHA-256 checksum and row count.
+//!
+//! The main types in this module are:
+//! - `Manifest`: The manifest of a directory.
+//! - `ManifestEntry`: The provenance of a single file.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The name of the manifest file in a directory of grade distribution files
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// The version of this tool, recorded in manifests
+pub const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The manifest of a directory of grade distribution files.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Manifest {
+    /// The dashboard the data was fetched from
+    pub source: String,
+    pub tool_version: String,
+    pub files: Vec<ManifestEntry>,
+}
+
+/// The provenance of a grade distribution file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub file_name: String,
+    pub academic_year: String,
+    /// The values selected in each dashboard filter, by field name
+    pub filters: BTreeMap<String, Vec<String>>,
+    /// The value of each dashboard parameter, by parameter name
+    pub parameters: BTreeMap<String, String>,
+    /// When the data was fetched from the dashboard, as an RFC 3339 UTC timestamp
+    pub fetched_at: String,
+    pub bytes: u64,
+    pub sha256: String,
+    /// The number of data rows, excluding the header
+    pub rows: u64,
+    /// The version of the tool that wrote the file
+    pub tool_version: String,
+    /// The entry of the file this one was derived from, e.g. the export a parsed file was parsed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derived_from: Option<Box<ManifestEntry>>,
+}
+
+/// The size, checksum and row count of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDigest {
+    pub bytes: u64,
+    pub sha256: String,
+    pub rows: u64,
+}
+
+impl FileDigest {
+    /// Describes a CSV file with a header row, reading it in chunks rather than all at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file.
+    /// * `delimiter` - The CSV delimiter, e.g. `b','` for exports and `b'\t'` for parsed files.
+    pub fn of_csv_file(path: &Path, delimiter: u8) -> std::io::Result<Self> {
+        let mut hasher: Sha256 = Sha256::new();
+        let bytes: u64 = std::io::copy(&mut File::open(path)?, &mut hasher)?;
+
+        Ok(FileDigest {
+            bytes,
+            sha256: hex(&hasher.finalize()),
+            rows: count_rows(File::open(path)?, delimiter),
+        })
+    }
+}
+
+/// Counts the records of a CSV file, excluding the header.
+fn count_rows<R: std::io::Read>(reader: R, delimiter: u8) -> u64 {
+    csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(reader)
+        .records()
+        .count() as u64
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl Manifest {
+    /// Loads the manifest of a directory.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(Manifest))` - If the directory has a manifest.
+    /// * `Ok(None)` - If it doesn't.
+    /// * `Err(Box<dyn std::error::Error>)` - If the manifest can't be read.
+    pub fn load(directory: &str) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        match std::fs::read_to_string(Path::new(directory).join(MANIFEST_FILE_NAME)) {
+            Ok(json) => Ok(Some(serde_json::from_str(&json)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Writes the manifest of a directory, replacing the previous one atomically.
+    pub fn save(&self, directory: &str) -> std::io::Result<()> {
+        let path = Path::new(directory).join(MANIFEST_FILE_NAME);
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(tmp_path, path)
+    }
+
+    /// The entry of a file, if it's listed.
+    pub fn entry(&self, file_name: &str) -> Option<&ManifestEntry> {
+        self.files.iter().find(|entry| entry.file_name == file_name)
+    }
+
+    /// Adds the entry of a file, replacing any previous entry of the same file.
+    pub fn upsert(&mut self, entry: ManifestEntry) {
+        self.files
+            .retain(|existing| existing.file_name != entry.file_name);
+        self.files.push(entry);
+        self.files.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    }
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp, e.g. `2024-03-14T09:26:53Z`.
+pub fn rfc3339_utc(time: SystemTime) -> String {
+    let seconds: u64 = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let seconds_of_day: u64 = seconds % 86_400;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date in the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z: i64 = days + 719_468;
+    let era: i64 = z.div_euclid(146_097);
+    let day_of_era: i64 = z - era * 146_097;
+    let year_of_era: i64 =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index: i64 = (5 * day_of_year + 2) / 153;
+    let day: u32 = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month: u32 = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year: i64 = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_rfc3339_timestamps() {
+        assert_eq!(rfc3339_utc(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            rfc3339_utc(UNIX_EPOCH + Duration::from_secs(1_710_408_413)),
+            "2024-03-14T09:26:53Z"
+        );
+        assert_eq!(
+            rfc3339_utc(UNIX_EPOCH + Duration::from_secs(951_825_600)),
+            "2000-02-29T12:00:00Z"
+        );
+    }
+
+    #[test]
+    fn digests_csv_files() {
+        let path: std::path::PathBuf =
+            std::env::temp_dir().join(format!("ut_grade_parser_digest_{}.csv", std::process::id()));
+        std::fs::write(&path, "a,b\n1,2\n3,4\n").unwrap();
+        let digest: FileDigest = FileDigest::of_csv_file(&path, b',').unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(digest.bytes, 12);
+        assert_eq!(digest.rows, 2);
+        assert_eq!(
+            digest.sha256,
+            "b9485148546419a0f6a85e8d708c923557c15d7f3c7d078ef1fa7f7c0f57d5a5"
+        );
+    }
+}
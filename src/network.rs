@@ -1,198 +1,3301 @@
-use std::fs::{create_dir_all, File};
-use std::io::Write;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
-use bytes::Bytes;
+use tokio::io::AsyncWriteExt;
 
-async fn get_session_id() -> Result<String, reqwest::Error> {
-    let url: &str = "https://iq-analytics.austin.utexas.edu/views/Gradedistributiondashboard/Externaldashboard-Crosstab?%3Aembed=y&%3AisGuestRedirectFromVizportal=n";
-    let response: reqwest::Response = reqwest::get(url).await?;
-    let body: String = response.text().await?;
+use crate::grade::GradeView;
+use crate::manifest::{rfc3339_utc, FileDigest, Manifest, ManifestEntry, TOOL_VERSION};
 
-    let document: scraper::Html = scraper::Html::parse_document(&body);
-    let selector: scraper::Selector = scraper::Selector::parse("#tsConfigContainer").unwrap();
-    let mut result: scraper::html::Select<'_, '_> = document.select(&selector);
+mod cross_check;
+mod error;
+mod progress;
+mod rate_limit;
+mod schema;
+mod session_cache;
+mod split;
+mod state;
+mod trace;
+mod traffic;
+mod transport;
 
-    // JSON.parse(document.getElementById('tsConfigContainer').value).sessionid;
-    let json_str: String = result.next().unwrap().inner_html();
-    let json_value: serde_json::Value = serde_json::from_str(&json_str).unwrap();
-    let session_id: &str = json_value["sessionid"].as_str().unwrap();
+use cross_check::{compare_grade_totals, GradeTotalMismatch};
+pub use error::NetworkError;
+use error::{json_str_at, snippet};
+pub use rate_limit::RateLimiter;
+pub use schema::MissingName;
+use schema::{check_schema, ExpectedNames};
+use session_cache::{CachedSession, MAX_SESSION_AGE};
+use split::{concatenate_parts, part_path, remove_parts};
+use state::DownloadState;
+pub use trace::Tracer;
+use traffic::{HttpRequest, RequestBody, REDACTED_SESSION_ID};
+pub use traffic::{Traffic, TrafficMode};
+pub use transport::TransportConfig;
 
-    Ok(session_id.to_string())
+/// A range of academic years to download.
+///
+/// Academic years are identified by the calendar year they start in, e.g. `2022` for 2022-2023.
+/// Unset bounds default to the first and last available academic year.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AcademicYearRange {
+    pub from: Option<u16>,
+    pub to: Option<u16>,
+    pub latest: bool,
 }
 
-async fn bootstrap(session_id: &str) -> Result<(), reqwest::Error> {
-    let url: String = format!("https://iq-analytics.austin.utexas.edu/vizql/w/Gradedistributiondashboard/v/Externaldashboard-Crosstab/bootstrapSession/sessions/{}", session_id);
-    let client: reqwest::Client = reqwest::Client::new();
+impl AcademicYearRange {
+    /// Selects the academic years in this range out of the available ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `available` - The start years of the available academic years, in filter order.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<usize>)` - The filter indices of the selected academic years.
+    /// * `Err(String)` - If the range is invalid or selects no available academic year.
+    fn select(&self, available: &[u16]) -> Result<Vec<usize>, String> {
+        if self.latest {
+            let latest = (0..available.len()).max_by_key(|&i| available[i]);
+            return latest
+                .map(|i| vec![i])
+                .ok_or_else(|| "No academic years are available".to_string());
+        }
 
-    client
-        .post(&url)
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .body("sheet_id=External%20dashboard-Crosstab")
-        .send()
-        .await?;
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                return Err(format!(
+                    "Invalid academic year range: {} is after {}",
+                    academic_year_label(from),
+                    academic_year_label(to)
+                ));
+            }
+        }
 
-    Ok(())
+        let indices: Vec<usize> = (0..available.len())
+            .filter(|&i| self.from.is_none_or(|from| available[i] >= from))
+            .filter(|&i| self.to.is_none_or(|to| available[i] <= to))
+            .collect();
+
+        if indices.is_empty() {
+            return Err(format!(
+                "No academic years available in the requested range (available: {} to {})",
+                available
+                    .iter()
+                    .min()
+                    .map_or("none".to_string(), |&year| academic_year_label(year)),
+                available
+                    .iter()
+                    .max()
+                    .map_or("none".to_string(), |&year| academic_year_label(year)),
+            ));
+        }
+
+        Ok(indices)
+    }
+}
+
+/// Formats an academic year from its start year, e.g. `2022` as `2022-2023`.
+fn academic_year_label(start_year: u16) -> String {
+    format!("{}-{}", start_year, start_year + 1)
+}
+
+/// Parses an academic year given either as its start year (`2022`) or as a span (`2022-2023`).
+///
+/// # Returns
+///
+/// * `Ok(u16)` - The start year of the academic year.
+/// * `Err(String)` - If the input is not a valid academic year.
+pub fn parse_academic_year(input: &str) -> Result<u16, String> {
+    let invalid = || {
+        format!(
+            "Invalid academic year: {} (expected e.g. 2022 or 2022-2023)",
+            input
+        )
+    };
+
+    match input.trim().split_once('-') {
+        Some((start, end)) => {
+            let start: u16 = start.trim().parse().map_err(|_| invalid())?;
+            let end: u16 = end.trim().parse().map_err(|_| invalid())?;
+            if end != start + 1 {
+                return Err(invalid());
+            }
+            Ok(start)
+        }
+        None => input.trim().parse().map_err(|_| invalid()),
+    }
+}
+
+/// Parses an academic year filter label such as `2022-2023`.
+///
+/// Unlike `parse_academic_year`, a bare start year is not accepted.
+fn parse_academic_year_label(label: &str) -> Option<u16> {
+    if label.contains('-') {
+        parse_academic_year(label).ok()
+    } else {
+        None
+    }
 }
 
-async fn get_sheet_doc_id(session_id: &str) -> Result<String, reqwest::Error> {
-    let url: String = format!("https://iq-analytics.austin.utexas.edu/vizql/w/Gradedistributiondashboard/v/Externaldashboard-Crosstab/sessions/{}/commands/tabsrv/export-crosstab-server-dialog", session_id);
-    let client: reqwest::Client = reqwest::Client::new();
+/// Splits a VizQL response into its JSON documents.
+///
+/// `bootstrapSession` answers with length-prefixed documents (`<length>;{...}<length>;{...}`),
+/// while the other commands answer with a single JSON document. The length isn't used to slice
+/// the body, since it doesn't count bytes once a document has non-ASCII text: each document ends
+/// where its JSON value ends.
+///
+/// # Returns
+///
+/// * `Ok(Vec<serde_json::Value>)` - The documents, in response order.
+/// * `Err(NetworkError::UnexpectedJson)` - If a document isn't valid JSON.
+fn parse_vizql_response(body: &str) -> Result<Vec<serde_json::Value>, NetworkError> {
+    let invalid = || NetworkError::unexpected_json("VizQL response", body);
+    let mut documents: Vec<serde_json::Value> = Vec::new();
+    let mut rest: &str = body.trim();
 
-    let raw_multipart: &str = r#"{"External dashboard-Crosstab":"/thumb/views/Gradedistributiondashboard/Externaldashboard-Crosstab","External dashboard-bar graph":"/thumb/views/Gradedistributiondashboard/Externaldashboard-bargraph"}"#;
-    let multipart: reqwest::multipart::Form =
-        reqwest::multipart::Form::new().text("thumbnailUris", raw_multipart);
-    let response: reqwest::Response = client.post(&url).multipart(multipart).send().await?;
+    while let Some((length, tail)) = rest.split_once(';') {
+        if length.is_empty() || !length.bytes().all(|byte| byte.is_ascii_digit()) {
+            break;
+        }
+        let mut values = serde_json::Deserializer::from_str(tail).into_iter::<serde_json::Value>();
+        let document: serde_json::Value =
+            values.next().ok_or_else(invalid)?.map_err(|_| invalid())?;
+        documents.push(document);
+        rest = tail[values.byte_offset()..].trim_start();
+    }
 
-    let json_response: serde_json::Value = response.text().await.unwrap().parse().unwrap();
-    // println!("{:#?}", json_response);
+    if documents.is_empty() {
+        documents.push(serde_json::from_str(body).map_err(|_| invalid())?);
+    } else if !rest.is_empty() {
+        return Err(invalid());
+    }
 
-    let sheet_doc_id: &serde_json::Value = &json_response["vqlCmdResponse"]["layoutStatus"]
-        ["applicationPresModel"]["presentationLayerNotification"][0]["presModelHolder"]
-        ["genExportCrosstabOptionsDialogPresModel"]["thumbnailSheetPickerItems"][0]["sheetdocId"];
+    Ok(documents)
+}
 
-    Ok(sheet_doc_id.as_str().unwrap().to_string())
+/// A categorical quick filter of the dashboard.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct DashboardFilter {
+    /// The filtered column, e.g. `COURSE_PREFIX`
+    pub field: String,
+    /// The name identifying the filter in commands, e.g. `[sqlproxy.1nikk2j199ysrw13cof5d1qn00ff].[none:COURSE_PREFIX:nk]`
+    pub global_field_name: String,
+    /// The filter values, in filter order, or empty if the response didn't list them
+    pub domain: Vec<String>,
 }
 
-async fn categorial_filter_all(
-    session_id: &str,
-    global_field_name: &str,
-) -> Result<(), reqwest::Error> {
-    let url: String = format!("https://iq-analytics.austin.utexas.edu/vizql/w/Gradedistributiondashboard/v/Externaldashboard-Crosstab/sessions/{}/commands/tabdoc/categorical-filter", session_id);
-    let client: reqwest::Client = reqwest::Client::new();
+/// Extracts the column from a global field name, e.g. `COURSE_PREFIX` from
+/// `[sqlproxy.1nikk2j199ysrw13cof5d1qn00ff].[none:COURSE_PREFIX:nk]`.
+fn field_of_global_field_name(global_field_name: &str) -> Option<&str> {
+    let (_, column) = global_field_name.trim_end_matches(']').rsplit_once(".[")?;
+    let mut parts = column.split(':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(_derivation), Some(field), Some(_role)) => Some(field),
+        _ => Some(column),
+    }
+}
 
-    // Select all semesters and select all courses
-    let multipart: reqwest::multipart::Form = reqwest::multipart::Form::new()
-        .text("visualIdPresModel", r#"{"worksheet":"Grade distribution - external","dashboard":"External dashboard-Crosstab"}"#)
-        .text("membershipTarget", "filter")
-        .text("globalFieldName", format!("[sqlproxy.1nikk2j199ysrw13cof5d1qn00ff].[none:{}:nk]", global_field_name.to_string()))
-        .text("filterValues", "[]")
-        .text("filterUpdateType", "filter-all");
+/// Collects the categorical quick filters of a VizQL presentation model.
+///
+/// Quick filters appear as `quickFilter.categoricalFilter` objects whose `fn` is the global field
+/// name of the filtered column and whose `domainTables[].domain[].label` are the filter values,
+/// in the same order used by `categorical-filter-by-index`.
+fn collect_filters(value: &serde_json::Value, filters: &mut Vec<DashboardFilter>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            if let Some(filter) = map.get("categoricalFilter") {
+                let global_field_name: &str = filter["fn"].as_str().unwrap_or_default();
+                if let Some(field) = field_of_global_field_name(global_field_name) {
+                    filters.push(DashboardFilter {
+                        field: field.to_string(),
+                        global_field_name: global_field_name.to_string(),
+                        domain: filter["domainTables"]
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .flat_map(|table| table["domain"].as_array().into_iter().flatten())
+                            .filter_map(|member| member["label"].as_str().map(str::to_string))
+                            .collect(),
+                    });
+                }
+            }
+            for child in map.values() {
+                collect_filters(child, filters);
+            }
+        }
+        serde_json::Value::Array(values) => {
+            for child in values {
+                collect_filters(child, filters);
+            }
+        }
+        _ => {}
+    }
+}
 
-    client.post(&url).multipart(multipart).send().await?;
+/// Lists the categorical filters of the dashboard from the VizQL responses received so far.
+///
+/// Filters are listed in the order they first appear, with the most recently received domain.
+///
+/// # Arguments
+///
+/// * `responses` - The bodies of the VizQL responses received so far, most recent last.
+///
+/// # Returns
+///
+/// * `Ok(Vec<DashboardFilter>)` - The filters, in dashboard order.
+/// * `Err(NetworkError::UnexpectedJson)` - If a response isn't valid JSON.
+fn dashboard_filters(responses: &[String]) -> Result<Vec<DashboardFilter>, NetworkError> {
+    let mut filters: Vec<DashboardFilter> = Vec::new();
+    for body in responses {
+        for document in parse_vizql_response(body)? {
+            let mut found: Vec<DashboardFilter> = Vec::new();
+            collect_filters(&document, &mut found);
+            for filter in found {
+                match filters
+                    .iter_mut()
+                    .find(|existing| existing.field == filter.field)
+                {
+                    Some(existing) if !filter.domain.is_empty() => existing.domain = filter.domain,
+                    Some(_) => {}
+                    None => filters.push(filter),
+                }
+            }
+        }
+    }
 
-    Ok(())
+    Ok(filters)
 }
 
-async fn categorical_filter_indices(
-    session_id: &str,
-    global_field_name: &str,
-    index: usize,
-) -> Result<(), reqwest::Error> {
-    let url: String = format!("https://iq-analytics.austin.utexas.edu/vizql/w/Gradedistributiondashboard/v/Externaldashboard-Crosstab/sessions/{}/commands/tabdoc/categorical-filter-by-index", session_id);
-    let client: reqwest::Client = reqwest::Client::new();
+/// Reads the domain of a categorical filter.
+///
+/// # Arguments
+///
+/// * `filters` - The categorical filters of the dashboard.
+/// * `field_name` - The filtered column, e.g. `COURSE_PREFIX`.
+///
+/// # Returns
+///
+/// * `Ok(Vec<String>)` - The filter values, in filter order.
+/// * `Err(NetworkError)` - If no filter on `field_name` is found.
+fn filter_domain(
+    filters: &[DashboardFilter],
+    field_name: &str,
+) -> Result<Vec<String>, NetworkError> {
+    filters
+        .iter()
+        .find(|filter| filter.field == field_name && !filter.domain.is_empty())
+        .map(|filter| filter.domain.clone())
+        .ok_or_else(|| NetworkError::InvalidFilterDomain {
+            field: field_name.to_string(),
+            reason: "the filter was not found in the dashboard".to_string(),
+        })
+}
 
-    // Select all semesters
-    let multipart: reqwest::multipart::Form = reqwest::multipart::Form::new()
-        .text("visualIdPresModel", r#"{"worksheet":"Grade distribution - external","dashboard":"External dashboard-Crosstab"}"#)
-        .text("membershipTarget", "filter")
-        .text("globalFieldName", format!("[sqlproxy.1nikk2j199ysrw13cof5d1qn00ff].[none:{}:nk]", global_field_name.to_string()))
-        .text("filterIndices", format!("[{}]", index))
-        .text("filterUpdateType", "filter-replace");
+/// Reads the available academic years from the `ACADEMIC_YEAR_SPAN` filter domain.
+///
+/// # Arguments
+///
+/// * `filters` - The categorical filters of the dashboard.
+///
+/// # Returns
+///
+/// * `Ok(Vec<(String, u16)>)` - The label and start year of each academic year, in filter order.
+/// * `Err(NetworkError)` - If the domain is missing or doesn't look like academic years.
+fn academic_year_domain(filters: &[DashboardFilter]) -> Result<Vec<(String, u16)>, NetworkError> {
+    let labels: Vec<String> = filter_domain(filters, "ACADEMIC_YEAR_SPAN")?;
 
-    client.post(&url).multipart(multipart).send().await?;
+    let invalid_labels: Vec<&str> = labels
+        .iter()
+        .filter(|label| parse_academic_year_label(label).is_none())
+        .map(String::as_str)
+        .collect();
+    if !invalid_labels.is_empty() {
+        return Err(NetworkError::InvalidFilterDomain {
+            field: "ACADEMIC_YEAR_SPAN".to_string(),
+            reason: format!("{:?} don't look like academic years", invalid_labels),
+        });
+    }
 
-    Ok(())
+    Ok(labels
+        .into_iter()
+        .map(|label| {
+            let start_year: u16 = parse_academic_year_label(&label).unwrap();
+            (label, start_year)
+        })
+        .collect())
 }
 
-async fn set_expanded_values(session_id: &str) -> Result<(), reqwest::Error> {
-    let url: String = format!("https://iq-analytics.austin.utexas.edu/vizql/w/Gradedistributiondashboard/v/Externaldashboard-Crosstab/sessions/{}/commands/tabdoc/set-parameter-value", session_id);
-    let client: reqwest::Client = reqwest::Client::new();
+/// The Tableau server hosting the grade distribution dashboard
+pub const DEFAULT_BASE_URL: &str = "https://iq-analytics.austin.utexas.edu";
 
-    // Select all semesters
-    let multipart: reqwest::multipart::Form = reqwest::multipart::Form::new()
-        .text("globalFieldName", "[Parameters].[Parameter 1]")
-        .text("valueString", "Expanded")
-        .text("useUsLocale", "false");
+/// The workbook containing the grade distribution dashboard
+pub const DEFAULT_WORKBOOK: &str = "Gradedistributiondashboard";
 
-    client.post(&url).multipart(multipart).send().await?;
+/// The crosstab view of the grade distribution dashboard
+pub const DEFAULT_VIEW: &str = "Externaldashboard-Crosstab";
 
-    Ok(())
+/// The sheet the session is bootstrapped on, and exported by default
+pub const SHEET_ID: &str = "External dashboard-Crosstab";
+
+/// The worksheet holding the grade distributions
+const WORKSHEET: &str = "Grade distribution - external";
+
+/// The worksheet and dashboard that filters are applied to
+const VISUAL_ID: &str =
+    r#"{"worksheet":"Grade distribution - external","dashboard":"External dashboard-Crosstab"}"#;
+
+/// The sheet of the bar graph, whose aggregate data is used to cross-check crosstab exports
+pub const BAR_GRAPH_SHEET: &str = "External dashboard-bar graph";
+
+/// The thumbnails of the sheets offered by the export crosstab dialog
+const THUMBNAIL_URIS: &str = r#"{"External dashboard-Crosstab":"/thumb/views/Gradedistributiondashboard/Externaldashboard-Crosstab","External dashboard-bar graph":"/thumb/views/Gradedistributiondashboard/Externaldashboard-bargraph"}"#;
+
+/// The parameter choosing how grade values are displayed
+const PARAMETER_1: &str = GradeView::PARAMETER;
+
+/// The value of `PARAMETER_1` listing every letter grade, e.g. `A-` and `B+`
+pub const DEFAULT_GRADE_VIEW: &str = "Expanded";
+
+/// The calculated field filtering semesters
+const SEMESTER_FIELD: &str = "Calculation_3161245480939225089";
+
+/// The names the downloader depends on, checked before anything is downloaded
+const EXPECTED_NAMES: ExpectedNames<'static> = ExpectedNames {
+    sheets: &[WORKSHEET, SHEET_ID],
+    filters: &["ACADEMIC_YEAR_SPAN", "COURSE_PREFIX", SEMESTER_FIELD],
+    parameters: &[PARAMETER_1],
+};
+
+/// How failed requests are retried.
+///
+/// Transient failures (connection errors, timeouts, `429` and `5xx` responses) are retried
+/// with an exponentially growing delay, starting at `initial_backoff` and capped at `max_backoff`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
 }
 
-async fn get_export_result_key(
-    session_id: &str,
-    sheet_doc_id: &str,
-) -> Result<String, reqwest::Error> {
-    let url: String = format!("https://iq-analytics.austin.utexas.edu/vizql/w/Gradedistributiondashboard/v/Externaldashboard-Crosstab/sessions/{}/commands/tabsrv/export-crosstab-to-csvserver", session_id);
-    let client: reqwest::Client = reqwest::Client::new();
+impl RetryPolicy {
+    /// The delay before retry number `attempt` (starting at 0).
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
 
-    let multipart: reqwest::multipart::Form = reqwest::multipart::Form::new()
-        .text("sheetdocId", sheet_doc_id.to_string())
-        .text("useTabs", "false")
-        .text("sendNotifications", "false");
-    let response: reqwest::Response = client.post(&url).multipart(multipart).send().await?;
+/// Where and how to open a `TableauSession`.
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    /// The URL of the Tableau server, e.g. `https://iq-analytics.austin.utexas.edu`
+    pub base_url: String,
+    /// The URL name of the workbook
+    pub workbook: String,
+    /// The URL name of the view
+    pub view: String,
+    pub retry: RetryPolicy,
+    pub transport: TransportConfig,
+    /// Records or replays the HTTP traffic of the session, shared by every session of a run
+    pub traffic: Option<Arc<Traffic>>,
+    /// Spaces out the requests of every session of a run
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Check that the dashboard still has the sheets, filters and parameters the downloader uses
+    pub preflight: bool,
+    /// Where the raw responses are written when the preflight check fails
+    pub diagnostics_directory: PathBuf,
+    /// The value of `[Parameters].[Parameter 1]`, choosing how grades are grouped in exports
+    pub grade_view: String,
+    /// The file a prepared session is saved to and reused from by later runs, if any
+    pub session_cache: Option<PathBuf>,
+    /// Logs, and possibly keeps, the HTTP traffic of every session of a run
+    pub tracer: Option<Arc<Tracer>>,
+}
 
-    let json_response: serde_json::Value = response.text().await.unwrap().parse().unwrap();
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            base_url: DEFAULT_BASE_URL.to_string(),
+            workbook: DEFAULT_WORKBOOK.to_string(),
+            view: DEFAULT_VIEW.to_string(),
+            retry: RetryPolicy::default(),
+            transport: TransportConfig::default(),
+            traffic: None,
+            rate_limiter: None,
+            preflight: true,
+            diagnostics_directory: PathBuf::from("diagnostics"),
+            grade_view: DEFAULT_GRADE_VIEW.to_string(),
+            session_cache: None,
+            tracer: None,
+        }
+    }
+}
 
-    let result_key: &serde_json::Value = &json_response["vqlCmdResponse"]["cmdResultList"][0]
-        ["commandReturn"]["exportResult"]["resultKey"];
+impl SessionConfig {
+    /// The URL of the view, as cited in download manifests.
+    pub fn view_url(&self) -> String {
+        format!("{}/views/{}/{}", self.base_url, self.workbook, self.view)
+    }
 
-    Ok(result_key.as_str().unwrap().to_string())
+    /// The session cache file, unless traffic is recorded or replayed, which needs fresh sessions.
+    fn session_cache_path(&self) -> Option<&Path> {
+        match self.traffic {
+            Some(_) => None,
+            None => self.session_cache.as_deref(),
+        }
+    }
+}
+
+/// A VizQL session on a Tableau view.
+///
+/// The session owns a single pooled HTTP client with a cookie store and exposes the VizQL
+/// commands used to filter and export the view as methods.
+pub struct TableauSession {
+    client: reqwest::Client,
+    /// The cookies of the client
+    cookies: Arc<reqwest::cookie::Jar>,
+    config: SessionConfig,
+    session_id: String,
+    /// The raw session config of the view page
+    ts_config: String,
+    /// When the session was opened, in seconds since the Unix epoch
+    created_at: u64,
+    /// The session as saved to the session cache, once it's prepared
+    cache: Mutex<Option<CachedSession>>,
+    /// The progress bar showing the downloads of the session, which messages about the session
+    /// are printed above
+    pb: progress::ProgressBar,
 }
 
-async fn download_exported_csv(
-    session_id: &str,
-    result_key: &str,
-) -> Result<Bytes, reqwest::Error> {
-    let url: String = format!("https://iq-analytics.austin.utexas.edu/vizql/w/Gradedistributiondashboard/v/Externaldashboard-Crosstab/tempfile/sessions/{}/?key={}", session_id, result_key);
-    let response: reqwest::Response = reqwest::get(&url).await?;
-    let body: Bytes = response.bytes().await?;
+impl TableauSession {
+    /// Opens a session on the view described by `config`.
+    pub async fn open(config: &SessionConfig) -> Result<Self, NetworkError> {
+        Self::open_with_progress(config, progress::ProgressBar::hidden()).await
+    }
+
+    /// Opens a session like `open`, showing its downloads and messages on `pb`.
+    async fn open_with_progress(
+        config: &SessionConfig,
+        pb: progress::ProgressBar,
+    ) -> Result<Self, NetworkError> {
+        let cookies: Arc<reqwest::cookie::Jar> = Arc::new(reqwest::cookie::Jar::default());
+        let mut session: TableauSession = TableauSession {
+            client: config.transport.build_client(cookies.clone())?,
+            cookies,
+            config: SessionConfig {
+                base_url: config.base_url.trim_end_matches('/').to_string(),
+                ..config.clone()
+            },
+            session_id: String::new(),
+            ts_config: String::new(),
+            created_at: session_cache::now(),
+            cache: Mutex::new(None),
+            pb,
+        };
+        session.ts_config = session.get_ts_config().await?;
+        // JSON.parse(document.getElementById('tsConfigContainer').value).sessionid;
+        session.session_id = json_str_at(&session.ts_config, "/sessionid")?;
+        if let Some(traffic) = &session.config.traffic {
+            traffic.redact_session_id(&session.session_id)?;
+        }
+
+        Ok(session)
+    }
+
+    /// Restores a session saved to the session cache, without checking that it's still alive.
+    fn resume(
+        config: &SessionConfig,
+        cached: &CachedSession,
+        pb: progress::ProgressBar,
+    ) -> Result<Self, NetworkError> {
+        let base_url: String = config.base_url.trim_end_matches('/').to_string();
+        let cookies: Arc<reqwest::cookie::Jar> = Arc::new(reqwest::cookie::Jar::default());
+        if let (Some(header), Ok(url)) = (&cached.cookies, reqwest::Url::parse(&base_url)) {
+            for cookie in header.split("; ") {
+                cookies.add_cookie_str(cookie, &url);
+            }
+        }
+
+        Ok(TableauSession {
+            client: config.transport.build_client(cookies.clone())?,
+            cookies,
+            config: SessionConfig {
+                base_url,
+                ..config.clone()
+            },
+            session_id: cached.session_id.clone(),
+            ts_config: cached.ts_config.clone(),
+            created_at: cached.created_at,
+            cache: Mutex::new(None),
+            pb,
+        })
+    }
+
+    /// Saves the session to the session cache, if any, with every filter selecting all its values.
+    ///
+    /// # Arguments
+    ///
+    /// * `filters` - The categorical filters of the dashboard.
+    fn save_to_cache(&self, filters: Vec<DashboardFilter>) -> Result<(), NetworkError> {
+        let Some(path) = self.config.session_cache_path() else {
+            return Ok(());
+        };
+        let cookies: Option<String> = reqwest::Url::parse(&self.config.base_url)
+            .ok()
+            .and_then(|url| reqwest::cookie::CookieStore::cookies(&*self.cookies, &url))
+            .and_then(|header| header.to_str().ok().map(str::to_string));
+        let cached: CachedSession = CachedSession {
+            view_url: self.config.view_url(),
+            session_id: self.session_id.clone(),
+            ts_config: self.ts_config.clone(),
+            cookies,
+            created_at: self.created_at,
+            filters,
+            narrowed_filters: Default::default(),
+        };
+        cached.save(path)?;
+        *self.cache.lock().unwrap() = Some(cached);
+
+        Ok(())
+    }
+
+    /// Records in the session cache that a filter no longer selects all its values, so that a
+    /// later run reusing the session resets it first.
+    fn mark_narrowed(&self, global_field_name: &str) -> Result<(), NetworkError> {
+        // Every export selects its academic year, so it never needs to be reset
+        if field_of_global_field_name(global_field_name) == Some("ACADEMIC_YEAR_SPAN") {
+            return Ok(());
+        }
+        let mut cache = self.cache.lock().unwrap();
+        let (Some(path), Some(cached)) = (self.config.session_cache_path(), cache.as_mut()) else {
+            return Ok(());
+        };
+        if cached
+            .narrowed_filters
+            .insert(global_field_name.to_string())
+        {
+            cached.save(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// The VizQL session id
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Builds the path of a VizQL endpoint of the view, e.g. `bootstrapSession/sessions/{id}`.
+    fn vizql_path(&self, path: &str) -> String {
+        format!(
+            "/vizql/w/{}/v/{}/{}",
+            self.config.workbook, self.config.view, path
+        )
+    }
+
+    /// Sends a request, retrying transient failures according to the retry policy.
+    async fn send(&self, request: &HttpRequest) -> Result<reqwest::Response, NetworkError> {
+        let mut attempt: u32 = 0;
+        loop {
+            let err: NetworkError = match self.send_once(request).await {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+            if !self.wait_before_retry(&err, attempt).await {
+                return Err(err);
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Waits out the backoff before retry number `attempt` (starting at 0) of a failed request.
+    ///
+    /// # Returns
+    ///
+    /// * `true` - If the failure is transient and the retry policy allows another attempt.
+    /// * `false` - If the request shouldn't be retried.
+    async fn wait_before_retry(&self, err: &NetworkError, attempt: u32) -> bool {
+        if !err.is_transient() || attempt >= self.config.retry.max_retries {
+            return false;
+        }
+
+        let delay: Duration = self.config.retry.backoff(attempt);
+        self.println(format!(
+            "{} (retrying in {:.1}s, attempt {}/{})",
+            err,
+            delay.as_secs_f32(),
+            attempt + 1,
+            self.config.retry.max_retries
+        ));
+        tokio::time::sleep(delay).await;
+
+        true
+    }
+
+    /// Sends a request once, over the network or from the replayed traffic.
+    async fn send_once(&self, request: &HttpRequest) -> Result<reqwest::Response, NetworkError> {
+        let url: String = format!("{}{}", self.config.base_url, request.path);
+        let started: SystemTime = SystemTime::now();
+        let timer: Instant = Instant::now();
+        let result: Result<reqwest::Response, NetworkError> = self.send_untraced(request).await;
+        let response: reqwest::Response = match &self.config.tracer {
+            Some(tracer) => {
+                tracer
+                    .trace(
+                        &self.config.base_url,
+                        request,
+                        started,
+                        timer,
+                        self.config.transport.read_timeout,
+                        &self.pb,
+                        result,
+                    )
+                    .await?
+            }
+            None => result?,
+        };
+
+        check_status(&url, response).await
+    }
+
+    /// Sends a request once without tracing it or checking its status.
+    async fn send_untraced(
+        &self,
+        request: &HttpRequest,
+    ) -> Result<reqwest::Response, NetworkError> {
+        let url: String = format!("{}{}", self.config.base_url, request.path);
+        match self.config.traffic.as_deref() {
+            Some(traffic) if traffic.is_replay() => traffic.replay(request),
+            traffic => {
+                if let Some(rate_limiter) = &self.config.rate_limiter {
+                    rate_limiter.wait().await;
+                }
+                let response: reqwest::Response = with_read_timeout(
+                    &url,
+                    self.config.transport.read_timeout,
+                    request.build(&self.client, &self.config.base_url).send(),
+                )
+                .await?;
+                match traffic {
+                    Some(traffic) => traffic.record(request, response).await,
+                    None => Ok(response),
+                }
+            }
+        }
+    }
 
-    Ok(body)
+    /// Prints a message about the session to stderr, above its progress bar if it's drawn.
+    fn println(&self, line: String) {
+        self.pb.suspend(|| eprintln!("{}", line));
+    }
+
+    /// Logs a line of `key=value` fields if requests are traced.
+    fn log(&self, fields: impl FnOnce() -> String) {
+        if let Some(tracer) = &self.config.tracer {
+            tracer.log(&self.pb, &fields());
+        }
+    }
+
+    /// Sends a request and reads the response body as text.
+    async fn send_text(&self, request: &HttpRequest) -> Result<String, NetworkError> {
+        let url: String = format!("{}{}", self.config.base_url, request.path);
+        let response: reqwest::Response = self.send(request).await?;
+
+        with_read_timeout(&url, self.config.transport.read_timeout, response.text()).await
+    }
+
+    /// Reads the session config held by the `#tsConfigContainer` element of the view page.
+    async fn get_ts_config(&self) -> Result<String, NetworkError> {
+        let request: HttpRequest = HttpRequest::get(format!(
+            "/views/{}/{}?%3Aembed=y&%3AisGuestRedirectFromVizportal=n",
+            self.config.workbook, self.config.view
+        ));
+        let body: String = self.send_text(&request).await?;
+
+        let document: scraper::Html = scraper::Html::parse_document(&body);
+        let selector: scraper::Selector = scraper::Selector::parse("#tsConfigContainer").unwrap();
+        let mut result: scraper::html::Select<'_, '_> = document.select(&selector);
+
+        let json_str: String = result
+            .next()
+            .ok_or_else(|| NetworkError::MissingConfigContainer {
+                url: format!("{}{}", self.config.base_url, request.path),
+            })?
+            .inner_html();
+
+        Ok(json_str)
+    }
+
+    /// Sends a VizQL command and returns the response body.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The namespaced command, e.g. `tabdoc/categorical-filter`.
+    /// * `fields` - The command arguments, sent as multipart form fields.
+    pub async fn command(
+        &self,
+        command: &str,
+        fields: &[(&str, String)],
+    ) -> Result<String, NetworkError> {
+        let request: HttpRequest = HttpRequest::post(
+            self.vizql_path(&format!(
+                "sessions/{}/commands/{}",
+                self.session_id, command
+            )),
+            RequestBody::Multipart(
+                fields
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.clone()))
+                    .collect(),
+            ),
+        );
+        self.send_text(&request).await
+    }
+
+    /// Bootstraps the session on a sheet and returns the bootstrap response body.
+    pub async fn bootstrap(&self, sheet_id: &str) -> Result<String, NetworkError> {
+        let request: HttpRequest = HttpRequest::post(
+            self.vizql_path(&format!("bootstrapSession/sessions/{}", self.session_id)),
+            RequestBody::Form(vec![("sheet_id".to_string(), sheet_id.to_string())]),
+        );
+        self.send_text(&request).await
+    }
+
+    /// Selects every value of a categorical filter and returns the response body.
+    pub async fn categorical_filter_all(
+        &self,
+        global_field_name: &str,
+    ) -> Result<String, NetworkError> {
+        self.command(
+            "tabdoc/categorical-filter",
+            &[
+                ("visualIdPresModel", VISUAL_ID.to_string()),
+                ("membershipTarget", "filter".to_string()),
+                ("globalFieldName", global_field_name.to_string()),
+                ("filterValues", "[]".to_string()),
+                ("filterUpdateType", "filter-all".to_string()),
+            ],
+        )
+        .await
+    }
+
+    /// Replaces the selection of a categorical filter with the values at `indices` of its domain
+    /// and returns the response body.
+    pub async fn categorical_filter_indices(
+        &self,
+        global_field_name: &str,
+        indices: &[usize],
+    ) -> Result<String, NetworkError> {
+        self.mark_narrowed(global_field_name)?;
+        self.command(
+            "tabdoc/categorical-filter-by-index",
+            &[
+                ("visualIdPresModel", VISUAL_ID.to_string()),
+                ("membershipTarget", "filter".to_string()),
+                ("globalFieldName", global_field_name.to_string()),
+                ("filterIndices", serde_json::json!(indices).to_string()),
+                ("filterUpdateType", "filter-replace".to_string()),
+            ],
+        )
+        .await
+    }
+
+    /// Replaces the selection of a categorical filter with `values`, given as labels of its domain,
+    /// and returns the response body.
+    pub async fn categorical_filter_values(
+        &self,
+        global_field_name: &str,
+        values: &[String],
+    ) -> Result<String, NetworkError> {
+        self.mark_narrowed(global_field_name)?;
+        self.command(
+            "tabdoc/categorical-filter",
+            &[
+                ("visualIdPresModel", VISUAL_ID.to_string()),
+                ("membershipTarget", "filter".to_string()),
+                ("globalFieldName", global_field_name.to_string()),
+                ("filterValues", serde_json::json!(values).to_string()),
+                ("filterUpdateType", "filter-replace".to_string()),
+            ],
+        )
+        .await
+    }
+
+    /// Sets the value of a parameter, e.g. `[Parameters].[Parameter 1]`, and returns the response body.
+    pub async fn set_parameter_value(
+        &self,
+        global_field_name: &str,
+        value: &str,
+    ) -> Result<String, NetworkError> {
+        self.command(
+            "tabdoc/set-parameter-value",
+            &[
+                ("globalFieldName", global_field_name.to_string()),
+                ("valueString", value.to_string()),
+                ("useUsLocale", "false".to_string()),
+            ],
+        )
+        .await
+    }
+
+    /// Opens the export crosstab dialog and returns the sheetdoc id of a sheet it offers.
+    ///
+    /// # Arguments
+    ///
+    /// * `sheet_name` - The name of the sheet, e.g. `External dashboard-Crosstab`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(String)` - The sheetdoc id of the sheet.
+    /// * `Err(NetworkError)` - If the dialog fails or doesn't offer the sheet.
+    pub async fn get_sheet_doc_id(&self, sheet_name: &str) -> Result<String, NetworkError> {
+        const ITEMS: &str = "/vqlCmdResponse/layoutStatus/applicationPresModel\
+                             /presentationLayerNotification/0/presModelHolder\
+                             /genExportCrosstabOptionsDialogPresModel/thumbnailSheetPickerItems";
+
+        let body: String = self
+            .command(
+                "tabsrv/export-crosstab-server-dialog",
+                &[("thumbnailUris", THUMBNAIL_URIS.to_string())],
+            )
+            .await?;
+
+        let json: serde_json::Value =
+            serde_json::from_str(&body).map_err(|_| NetworkError::unexpected_json("", &body))?;
+        let items: &Vec<serde_json::Value> = json
+            .pointer(ITEMS)
+            .and_then(serde_json::Value::as_array)
+            .ok_or_else(|| NetworkError::unexpected_json(ITEMS, &body))?;
+
+        match items.iter().find(|item| item["sheetName"] == sheet_name) {
+            Some(item) => item["sheetdocId"]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| {
+                    NetworkError::unexpected_json(&format!("{}/sheetdocId", ITEMS), &body)
+                }),
+            None => Err(NetworkError::UnknownSheet {
+                name: sheet_name.to_string(),
+                available: items
+                    .iter()
+                    .filter_map(|item| item["sheetName"].as_str())
+                    .map(str::to_string)
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Exports a sheet as CSV on the server and returns the result key of the export.
+    pub async fn get_export_result_key(&self, sheet_doc_id: &str) -> Result<String, NetworkError> {
+        let body: String = self
+            .command(
+                "tabsrv/export-crosstab-to-csvserver",
+                &[
+                    ("sheetdocId", sheet_doc_id.to_string()),
+                    ("useTabs", "false".to_string()),
+                    ("sendNotifications", "false".to_string()),
+                ],
+            )
+            .await?;
+
+        json_str_at(
+            &body,
+            "/vqlCmdResponse/cmdResultList/0/commandReturn/exportResult/resultKey",
+        )
+    }
+
+    /// Streams the result of an export into a file, retrying downloads that are cut short.
+    ///
+    /// # Arguments
+    ///
+    /// * `result_key` - The result key of the export.
+    /// * `destination` - The file to write, only created once the whole export has arrived.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(u64)` - The size of the file.
+    /// * `Err(NetworkError)` - If the download or writing the file fails.
+    pub async fn download_exported_csv(
+        &self,
+        result_key: &str,
+        destination: &Path,
+    ) -> Result<u64, NetworkError> {
+        let request: HttpRequest = HttpRequest::get(self.vizql_path(&format!(
+            "tempfile/sessions/{}/?key={}",
+            self.session_id, result_key
+        )));
+        let url: String = format!("{}{}", self.config.base_url, request.path);
+
+        let mut attempt: u32 = 0;
+        loop {
+            let response: reqwest::Response = self.send(&request).await?;
+            let err: NetworkError = match stream_to_file(
+                &url,
+                response,
+                destination,
+                self.config.transport.read_timeout,
+                &self.pb,
+            )
+            .await
+            {
+                Ok(bytes) => return Ok(bytes),
+                Err(err) => err,
+            };
+            if !self.wait_before_retry(&err, attempt).await {
+                return Err(err);
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Exports a sheet with the current filters as a CSV file.
+    ///
+    /// # Arguments
+    ///
+    /// * `sheet_name` - The name of the sheet in the export crosstab dialog.
+    /// * `destination` - The file to write.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(u64)` - The size of the file.
+    /// * `Err(NetworkError)` - If the export fails.
+    pub async fn export_csv(
+        &self,
+        sheet_name: &str,
+        destination: &Path,
+    ) -> Result<u64, NetworkError> {
+        let sheet_doc_id: String = self.get_sheet_doc_id(sheet_name).await?;
+        let result_key: String = self.get_export_result_key(&sheet_doc_id).await?;
+        self.log(|| {
+            format!(
+                "sheet={:?} sheet_doc_id={} result_key={}",
+                sheet_name, sheet_doc_id, result_key
+            )
+        });
+        self.download_exported_csv(&result_key, destination).await
+    }
+}
+
+/// Streams a response body into a file, waiting at most `read_timeout` for each chunk.
+///
+/// The body is written chunk by chunk to a `.part` file next to `destination`, which is renamed
+/// into place only after the whole body, and the length announced by `Content-Length` if any, has
+/// arrived. A failed download removes the `.part` file and leaves `destination` untouched.
+async fn stream_to_file(
+    url: &str,
+    mut response: reqwest::Response,
+    destination: &Path,
+    read_timeout: Duration,
+    pb: &progress::ProgressBar,
+) -> Result<u64, NetworkError> {
+    let mut partial_path: std::ffi::OsString = destination.as_os_str().to_owned();
+    partial_path.push(".part");
+    let partial_path: PathBuf = PathBuf::from(partial_path);
+
+    let expected: Option<u64> = response.content_length();
+    pb.set_style(transfer_style(expected.is_some()));
+    pb.set_length(expected.unwrap_or(0));
+    pb.set_position(0);
+
+    let result: Result<u64, NetworkError> = async {
+        let mut file: tokio::fs::File = tokio::fs::File::create(&partial_path)
+            .await
+            .map_err(|err| NetworkError::file(&partial_path, err))?;
+        let mut received: u64 = 0;
+        while let Some(chunk) = with_read_timeout(url, read_timeout, response.chunk()).await? {
+            file.write_all(&chunk)
+                .await
+                .map_err(|err| NetworkError::file(&partial_path, err))?;
+            received += chunk.len() as u64;
+            pb.set_position(received);
+        }
+        file.sync_all()
+            .await
+            .map_err(|err| NetworkError::file(&partial_path, err))?;
+
+        if let Some(expected) = expected.filter(|&expected| expected != received) {
+            return Err(NetworkError::IncompleteBody {
+                url: url.to_string(),
+                expected,
+                received,
+            });
+        }
+        tokio::fs::rename(&partial_path, destination)
+            .await
+            .map_err(|err| NetworkError::file(destination, err))?;
+
+        Ok(received)
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&partial_path).await;
+    }
+    result
+}
+
+/// Waits for a network read, failing with `NetworkError::Timeout` once `timeout` has elapsed.
+async fn with_read_timeout<T>(
+    url: &str,
+    timeout: Duration,
+    read: impl std::future::Future<Output = Result<T, reqwest::Error>>,
+) -> Result<T, NetworkError> {
+    match tokio::time::timeout(timeout, read).await {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(NetworkError::Timeout {
+            url: url.to_string(),
+            after: timeout,
+        }),
+    }
+}
+
+/// The style of the progress bar of a worker.
+fn worker_style() -> progress::ProgressStyle {
+    progress::ProgressStyle::with_template("{spinner} [session {prefix}] {msg}").unwrap()
+}
+
+/// The style of the progress bar of a worker while it downloads a file.
+///
+/// # Arguments
+///
+/// * `known_length` - Whether the size of the file is known, and can be shown.
+fn transfer_style(known_length: bool) -> progress::ProgressStyle {
+    progress::ProgressStyle::with_template(if known_length {
+        "{spinner} [session {prefix}] {msg} {bytes}/{total_bytes} ({bytes_per_sec})"
+    } else {
+        "{spinner} [session {prefix}] {msg} {bytes} ({bytes_per_sec})"
+    })
+    .unwrap()
+}
+
+/// Turns a non-2xx response into a `NetworkError::Status`.
+async fn check_status(
+    url: &str,
+    response: reqwest::Response,
+) -> Result<reqwest::Response, NetworkError> {
+    let status: reqwest::StatusCode = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let url: String = url.to_string();
+    let body: String = response.text().await.unwrap_or_default();
+    Err(NetworkError::Status {
+        url,
+        status,
+        snippet: snippet(&body),
+    })
+}
+
+/// How downloads are split into files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Partition {
+    /// One file per academic year
+    #[default]
+    Year,
+    /// One file per academic year and department (`COURSE_PREFIX`)
+    Department,
+}
+
+/// A selection of values of a categorical filter, e.g. `Calculation_3161245480939225089=Spring`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldFilter {
+    /// The filtered column
+    pub field: String,
+    pub values: Vec<String>,
 }
 
-async fn export_csv(session_id: &str) -> Result<Bytes, reqwest::Error> {
-    let sheet_doc_id: String = get_sheet_doc_id(session_id).await?;
-    // println!("Sheet Doc ID: {}", sheet_doc_id);
-    let result_key: String = get_export_result_key(session_id, &sheet_doc_id).await?;
-    // println!("Result Key: {}", result_key);
-    let csv = download_exported_csv(session_id, &result_key).await?;
+/// Parses a filter given as `FIELD=VALUE[,VALUE...]`.
+pub fn parse_field_filter(input: &str) -> Result<FieldFilter, String> {
+    let invalid = || {
+        format!(
+            "Invalid filter: {} (expected FIELD=VALUE[,VALUE...])",
+            input
+        )
+    };
+
+    let (field, values) = input.split_once('=').ok_or_else(invalid)?;
+    let values: Vec<String> = values
+        .split(',')
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect();
+    if field.trim().is_empty() || values.is_empty() {
+        return Err(invalid());
+    }
 
-    Ok(csv)
+    Ok(FieldFilter {
+        field: field.trim().to_string(),
+        values,
+    })
 }
 
-pub async fn fetch_and_download_grade_distributions() -> Result<(), Box<dyn std::error::Error>> {
-    let session_id: String = get_session_id().await?;
-    println!("Session ID: {}", session_id);
+/// Options of `fetch_and_download_grade_distributions`.
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    pub years: AcademicYearRange,
+    /// Only download these department prefixes (e.g. `C S`), or every department if empty
+    pub departments: Vec<String>,
+    pub partition: Partition,
+    /// Extra filters applied to every exported file
+    pub filters: Vec<FieldFilter>,
+    pub session: SessionConfig,
+    /// The sheet exported to each file, as named in the export crosstab dialog
+    pub sheet: String,
+    /// Also export the bar graph of each file and check that its grade totals match
+    pub cross_check: bool,
+    /// Re-export a file split by department or semester when its export fails or is too short
+    pub split_exports: bool,
+    /// The fewest rows an exported file may have before it's re-exported split
+    pub min_rows: u64,
+    /// The directory the exported CSV files are written to
+    pub output_directory: String,
+    /// Skip files that a previous run already downloaded completely
+    pub resume: bool,
+    /// The number of sessions downloading concurrently
+    pub jobs: usize,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        DownloadOptions {
+            years: AcademicYearRange::default(),
+            departments: Vec::new(),
+            partition: Partition::default(),
+            filters: Vec::new(),
+            session: SessionConfig::default(),
+            sheet: SHEET_ID.to_string(),
+            cross_check: false,
+            split_exports: true,
+            min_rows: 1,
+            output_directory: "out".to_string(),
+            resume: false,
+            jobs: 1,
+        }
+    }
+}
 
-    println!("[1/4] Bootstrapping session");
-    bootstrap(&session_id).await?;
+/// The filter domains that downloads are sliced by.
+#[derive(Debug, Clone)]
+struct FilterDomains {
+    /// The label and start year of each academic year
+    academic_years: Vec<(String, u16)>,
+    /// The department prefixes
+    course_prefixes: Vec<String>,
+    /// Every categorical filter of the dashboard
+    filters: Vec<DashboardFilter>,
+}
+
+impl FilterDomains {
+    /// Reads the domains from the categorical filters of the dashboard.
+    fn from_filters(filters: Vec<DashboardFilter>) -> Result<Self, NetworkError> {
+        Ok(FilterDomains {
+            academic_years: academic_year_domain(&filters)?,
+            course_prefixes: filter_domain(&filters, "COURSE_PREFIX")?,
+            filters,
+        })
+    }
+
+    /// Looks up the filter on a column.
+    fn filter(&self, field: &str) -> Result<&DashboardFilter, NetworkError> {
+        self.filters
+            .iter()
+            .find(|filter| filter.field == field)
+            .ok_or_else(|| NetworkError::InvalidFilterDomain {
+                field: field.to_string(),
+                reason: "the filter was not found in the dashboard".to_string(),
+            })
+    }
+}
+
+/// A part of the grade distributions exported to a single file.
+#[derive(Debug, Clone)]
+struct Slice {
+    /// The index of the academic year in the `ACADEMIC_YEAR_SPAN` filter domain
+    year_index: usize,
+    /// The indices of the departments in the `COURSE_PREFIX` filter domain, or `None` for all
+    department_indices: Option<Vec<usize>>,
+    academic_year: String,
+    /// The selected department prefixes, or empty for all
+    departments: Vec<String>,
+    /// The extra filters, with values as labels of the filter domains
+    filters: Vec<FieldFilter>,
+    file_name: String,
+    description: String,
+}
+
+impl Slice {
+    /// Looks up the academic year and departments of the slice again by their labels, in the
+    /// filter domains of another session, whose indices may differ.
+    fn relocate(&self, domains: &FilterDomains) -> Result<Slice, NetworkError> {
+        let year_index: usize = domains
+            .academic_years
+            .iter()
+            .position(|(label, _)| *label == self.academic_year)
+            .ok_or_else(|| NetworkError::InvalidFilterDomain {
+                field: "ACADEMIC_YEAR_SPAN".to_string(),
+                reason: format!(
+                    "the academic year {} is no longer listed",
+                    self.academic_year
+                ),
+            })?;
+        let department_indices: Option<Vec<usize>> = match &self.department_indices {
+            None => None,
+            Some(_) => Some(
+                department_indices(&self.departments, &domains.course_prefixes).map_err(
+                    |reason| NetworkError::InvalidFilterDomain {
+                        field: "COURSE_PREFIX".to_string(),
+                        reason,
+                    },
+                )?,
+            ),
+        };
+
+        Ok(Slice {
+            year_index,
+            department_indices,
+            ..self.clone()
+        })
+    }
+
+    /// Describes the exported file of the slice for the download manifest.
+    fn manifest_entry(
+        &self,
+        digest: FileDigest,
+        fetched_at: String,
+        grade_view: &str,
+    ) -> ManifestEntry {
+        let all: Vec<String> = vec!["(All)".to_string()];
+        let departments: Vec<String> = if self.departments.is_empty() {
+            all.clone()
+        } else {
+            self.departments.clone()
+        };
+
+        let mut filters: BTreeMap<String, Vec<String>> = [
+            (
+                "ACADEMIC_YEAR_SPAN".to_string(),
+                vec![self.academic_year.clone()],
+            ),
+            ("COURSE_PREFIX".to_string(), departments),
+            (SEMESTER_FIELD.to_string(), all),
+        ]
+        .into();
+        for filter in &self.filters {
+            filters.insert(filter.field.clone(), filter.values.clone());
+        }
+
+        ManifestEntry {
+            file_name: self.file_name.clone(),
+            academic_year: self.academic_year.clone(),
+            filters,
+            parameters: [(PARAMETER_1.to_string(), grade_view.to_string())].into(),
+            fetched_at,
+            bytes: digest.bytes,
+            sha256: digest.sha256,
+            rows: digest.rows,
+            tool_version: TOOL_VERSION.to_string(),
+            derived_from: None,
+        }
+    }
+
+    /// Splits the slice into smaller slices that together export the same rows.
+    ///
+    /// A slice is split by department if it selects several or all departments, otherwise by
+    /// semester if it selects several or all semesters.
+    ///
+    /// # Returns
+    ///
+    /// * `Some((&str, Vec<Slice>))` - The column the slice is split by, and the parts.
+    /// * `None` - If the slice selects a single department and a single semester.
+    fn split(&self, domains: &FilterDomains) -> Option<(&'static str, Vec<Slice>)> {
+        let department_indices: Vec<usize> = match &self.department_indices {
+            None => (0..domains.course_prefixes.len()).collect(),
+            Some(indices) => indices.clone(),
+        };
+        if department_indices.len() > 1 {
+            let parts: Vec<Slice> = department_indices
+                .into_iter()
+                .map(|index| {
+                    let department: &str = &domains.course_prefixes[index];
+                    Slice {
+                        department_indices: Some(vec![index]),
+                        departments: vec![department.to_string()],
+                        description: format!("{} {}", self.description, department),
+                        ..self.clone()
+                    }
+                })
+                .collect();
+            return Some(("COURSE_PREFIX", parts));
+        }
+
+        let semesters: Vec<String> = match self
+            .filters
+            .iter()
+            .find(|filter| filter.field == SEMESTER_FIELD)
+        {
+            Some(filter) => filter.values.clone(),
+            None => domains.filter(SEMESTER_FIELD).ok()?.domain.clone(),
+        };
+        if semesters.len() <= 1 {
+            return None;
+        }
+        let parts: Vec<Slice> = semesters
+            .into_iter()
+            .map(|semester| {
+                let mut filters: Vec<FieldFilter> = self
+                    .filters
+                    .iter()
+                    .filter(|filter| filter.field != SEMESTER_FIELD)
+                    .cloned()
+                    .collect();
+                filters.push(FieldFilter {
+                    field: SEMESTER_FIELD.to_string(),
+                    values: vec![semester.clone()],
+                });
+                Slice {
+                    filters,
+                    description: format!("{} {}", self.description, semester),
+                    ..self.clone()
+                }
+            })
+            .collect();
+
+        Some((SEMESTER_FIELD, parts))
+    }
+}
+
+/// Turns filter values into a file name suffix, e.g. `["C S", "M"]` into `_C_S_M`.
+fn file_name_suffix(values: &[&str]) -> String {
+    values
+        .iter()
+        .map(|value| {
+            let slug: String = value
+                .trim()
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect();
+            format!("_{}", slug)
+        })
+        .collect()
+}
+
+/// Resolves department prefixes to their indices in the `COURSE_PREFIX` filter domain.
+fn department_indices(
+    departments: &[String],
+    course_prefixes: &[String],
+) -> Result<Vec<usize>, String> {
+    let mut unknown: Vec<&str> = Vec::new();
+    let mut indices: Vec<usize> = Vec::new();
+    for department in departments {
+        match course_prefixes
+            .iter()
+            .position(|prefix| prefix.trim().eq_ignore_ascii_case(department.trim()))
+        {
+            Some(index) if !indices.contains(&index) => indices.push(index),
+            Some(_) => {}
+            None => unknown.push(department),
+        }
+    }
+
+    if !unknown.is_empty() {
+        return Err(format!(
+            "Unknown department prefixes {:?} (available: {:?})",
+            unknown, course_prefixes
+        ));
+    }
+
+    Ok(indices)
+}
+
+/// Resolves filters against the filter domains of the dashboard.
+///
+/// Fields and values are matched case-insensitively and replaced by their names in the dashboard,
+/// and filters on the same field are merged.
+///
+/// # Returns
+///
+/// * `Ok(Vec<FieldFilter>)` - The filters, with values as labels of the filter domains.
+/// * `Err(String)` - If a field isn't a filter of the dashboard, or a value isn't in its domain.
+fn resolve_field_filters(
+    filters: &[FieldFilter],
+    domains: &FilterDomains,
+) -> Result<Vec<FieldFilter>, String> {
+    let mut resolved: Vec<FieldFilter> = Vec::new();
+    for filter in filters {
+        let field: &str = filter.field.trim();
+        if field.eq_ignore_ascii_case("ACADEMIC_YEAR_SPAN") {
+            return Err(format!(
+                "Select academic years with --from, --to or --latest instead of --filter {}",
+                field
+            ));
+        }
+        if field.eq_ignore_ascii_case("COURSE_PREFIX") {
+            return Err(format!(
+                "Select departments with --department instead of --filter {}",
+                field
+            ));
+        }
+
+        let dashboard_filter: &DashboardFilter = domains
+            .filters
+            .iter()
+            .find(|candidate| candidate.field.eq_ignore_ascii_case(field))
+            .ok_or_else(|| {
+                format!(
+                    "Unknown filter field {:?} (available: {:?})",
+                    field,
+                    domains
+                        .filters
+                        .iter()
+                        .map(|candidate| candidate.field.as_str())
+                        .collect::<Vec<&str>>()
+                )
+            })?;
+
+        let mut values: Vec<String> = Vec::new();
+        let mut unknown: Vec<&str> = Vec::new();
+        for value in &filter.values {
+            match dashboard_filter
+                .domain
+                .iter()
+                .find(|label| label.trim().eq_ignore_ascii_case(value.trim()))
+            {
+                Some(label) => values.push(label.clone()),
+                None => unknown.push(value),
+            }
+        }
+        if !unknown.is_empty() {
+            return Err(format!(
+                "Unknown {} values {:?} (available: {:?})",
+                dashboard_filter.field, unknown, dashboard_filter.domain
+            ));
+        }
+
+        match resolved
+            .iter_mut()
+            .find(|existing| existing.field == dashboard_filter.field)
+        {
+            Some(existing) => existing.values.extend(values),
+            None => resolved.push(FieldFilter {
+                field: dashboard_filter.field.clone(),
+                values,
+            }),
+        }
+    }
+    for filter in &mut resolved {
+        let mut seen: Vec<String> = Vec::new();
+        filter.values.retain(|value| {
+            let new: bool = !seen.contains(value);
+            seen.push(value.clone());
+            new
+        });
+    }
+
+    Ok(resolved)
+}
+
+/// Plans the files to download for the selected academic years.
+fn plan_slices(
+    options: &DownloadOptions,
+    domains: &FilterDomains,
+    year_indices: &[usize],
+) -> Result<Vec<Slice>, String> {
+    let selected_departments: Option<Vec<usize>> = if options.departments.is_empty() {
+        None
+    } else {
+        Some(department_indices(
+            &options.departments,
+            &domains.course_prefixes,
+        )?)
+    };
+
+    let department_groups: Vec<Option<Vec<usize>>> = match options.partition {
+        Partition::Year => vec![selected_departments],
+        Partition::Department => selected_departments
+            .unwrap_or_else(|| (0..domains.course_prefixes.len()).collect())
+            .into_iter()
+            .map(|index| Some(vec![index]))
+            .collect(),
+    };
+
+    let filters: Vec<FieldFilter> = resolve_field_filters(&options.filters, domains)?;
+    let filter_values: Vec<&str> = filters
+        .iter()
+        .flat_map(|filter| filter.values.iter().map(String::as_str))
+        .collect();
+
+    let mut slices: Vec<Slice> = Vec::new();
+    for &year_index in year_indices {
+        let academic_year: &str = &domains.academic_years[year_index].0;
+        for department_indices in &department_groups {
+            let departments: Vec<&str> = department_indices
+                .iter()
+                .flatten()
+                .map(|&index| domains.course_prefixes[index].as_str())
+                .collect();
+            slices.push(Slice {
+                year_index,
+                department_indices: department_indices.clone(),
+                academic_year: academic_year.to_string(),
+                departments: departments
+                    .iter()
+                    .map(|department| department.to_string())
+                    .collect(),
+                filters: filters.clone(),
+                file_name: format!(
+                    "grade_distributions_{}{}{}.csv",
+                    academic_year,
+                    file_name_suffix(&departments),
+                    file_name_suffix(&filter_values)
+                ),
+                description: [academic_year.to_string()]
+                    .into_iter()
+                    .chain((!departments.is_empty()).then(|| departments.join(", ")))
+                    .chain((!filter_values.is_empty()).then(|| filter_values.join(", ")))
+                    .collect::<Vec<String>>()
+                    .join(" "),
+            });
+        }
+    }
+
+    Ok(slices)
+}
+
+/// Opens a session and prepares it for exporting: bootstraps it, selects all semesters and
+/// courses, and expands the grade values.
+///
+/// # Arguments
+///
+/// * `config` - Where and how to open the session.
+/// * `pb` - The progress bar reporting the preparation steps.
+///
+/// # Returns
+///
+/// * `Ok((TableauSession, FilterDomains))` - The session and the domains of its filters.
+/// * `Err(NetworkError)` - If any step fails.
+async fn prepare_session(
+    config: &SessionConfig,
+    pb: &progress::ProgressBar,
+) -> Result<(TableauSession, FilterDomains), NetworkError> {
+    pb.set_message("Opening session");
+    let session: TableauSession = TableauSession::open_with_progress(config, pb.clone()).await?;
+
+    pb.set_message(format!(
+        "[1/3] Bootstrapping session {}",
+        session.session_id()
+    ));
+    let mut responses: Vec<String> = vec![session.bootstrap(SHEET_ID).await?];
+    let filters: Vec<DashboardFilter> = dashboard_filters(&responses)?;
+    if config.preflight {
+        preflight(
+            &session,
+            &responses[0],
+            &filters,
+            &config.diagnostics_directory,
+        )?;
+    }
 
     // filter sheet
-    println!("[2/4] Categorial filter all. Select all semesters and select all courses");
-    categorial_filter_all(&session_id, "Calculation_3161245480939225089").await?;
-    categorial_filter_all(&session_id, "COURSE_PREFIX").await?;
+    pb.set_message("[2/3] Categorial filter all. Select all semesters and select all courses");
+    for field in [SEMESTER_FIELD, "COURSE_PREFIX"] {
+        if let Some(filter) = filters.iter().find(|filter| filter.field == field) {
+            responses.push(
+                session
+                    .categorical_filter_all(&filter.global_field_name)
+                    .await?,
+            );
+        }
+    }
+
+    let domains: FilterDomains = FilterDomains::from_filters(dashboard_filters(&responses)?)?;
+
+    pb.set_message(format!("[3/3] Set the {} grade view", config.grade_view));
+    session
+        .set_parameter_value(PARAMETER_1, &config.grade_view)
+        .await?;
+    session.save_to_cache(domains.filters.clone())?;
+
+    Ok((session, domains))
+}
+
+/// Prepares a session like `prepare_session`, reusing the session of the session cache if it's
+/// still alive.
+///
+/// A cached session is checked by setting the grade view, which fails once the server has
+/// discarded the session, and the filters a previous run narrowed are reset. If anything fails,
+/// a new session is prepared instead.
+async fn prepare_cached_session(
+    config: &SessionConfig,
+    pb: &progress::ProgressBar,
+) -> Result<(TableauSession, FilterDomains), NetworkError> {
+    let cached: Option<CachedSession> = match config.session_cache_path() {
+        Some(path) => CachedSession::load(path)?,
+        None => None,
+    };
+    if let Some(cached) = cached {
+        if cached.view_url == config.view_url() && cached.age() < MAX_SESSION_AGE {
+            pb.set_message(format!("Reusing session {}", cached.session_id));
+            match resume_session(config, cached, pb).await {
+                Ok(prepared) => return Ok(prepared),
+                Err(err) => pb.println(format!(
+                    "The cached session can't be reused ({}), opening a new session",
+                    err
+                )),
+            }
+        }
+    }
+
+    prepare_session(config, pb).await
+}
+
+/// Restores a cached session and prepares it for exporting.
+async fn resume_session(
+    config: &SessionConfig,
+    cached: CachedSession,
+    pb: &progress::ProgressBar,
+) -> Result<(TableauSession, FilterDomains), NetworkError> {
+    let session: TableauSession = TableauSession::resume(config, &cached, pb.clone())?;
+    session
+        .set_parameter_value(PARAMETER_1, &config.grade_view)
+        .await?;
+    for global_field_name in &cached.narrowed_filters {
+        session.categorical_filter_all(global_field_name).await?;
+    }
+    let domains: FilterDomains = FilterDomains::from_filters(cached.filters.clone())?;
+    session.save_to_cache(cached.filters)?;
+
+    Ok((session, domains))
+}
+
+/// Checks that the bootstrap response still has the sheets, filters and parameters the downloader
+/// uses, so that a changed dashboard is reported up front rather than as a failing command.
+///
+/// # Arguments
+///
+/// * `session` - The bootstrapped session.
+/// * `bootstrap` - The body of the bootstrap response.
+/// * `filters` - The categorical filters found in the bootstrap response.
+/// * `diagnostics_directory` - Where to write the raw session config and bootstrap response if
+///   anything is missing.
+///
+/// # Returns
+///
+/// * `Ok(())` - If every expected name was found.
+/// * `Err(NetworkError::SchemaDrift)` - With the missing names and close matches.
+fn preflight(
+    session: &TableauSession,
+    bootstrap: &str,
+    filters: &[DashboardFilter],
+    diagnostics_directory: &Path,
+) -> Result<(), NetworkError> {
+    let fields: Vec<String> = filters.iter().map(|filter| filter.field.clone()).collect();
+    let missing: Vec<MissingName> =
+        check_schema(&parse_vizql_response(bootstrap)?, &fields, &EXPECTED_NAMES);
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let redact = |body: &str| body.replace(session.session_id(), REDACTED_SESSION_ID);
+    let report: String = missing.iter().map(|name| format!("{}\n", name)).collect();
+    let written: std::io::Result<()> = create_dir_all(diagnostics_directory).and_then(|()| {
+        std::fs::write(
+            diagnostics_directory.join("tsConfigContainer.json"),
+            redact(&session.ts_config),
+        )?;
+        std::fs::write(
+            diagnostics_directory.join("bootstrapSession.txt"),
+            redact(bootstrap),
+        )?;
+        std::fs::write(diagnostics_directory.join("missing.txt"), report)
+    });
+    let diagnostics: Option<String> = match written {
+        Ok(()) => Some(diagnostics_directory.display().to_string()),
+        Err(err) => {
+            eprintln!(
+                "Failed to write diagnostics to {}: {}",
+                diagnostics_directory.display(),
+                err
+            );
+            None
+        }
+    };
+
+    Err(NetworkError::SchemaDrift {
+        missing,
+        diagnostics,
+    })
+}
+
+/// The directory, relative to the output directory, that bar graph exports are written to
+const BAR_GRAPH_DIRECTORY: &str = "bar_graph";
+
+/// Exports a slice of the grade distributions as a CSV file and returns its size.
+///
+/// # Arguments
+///
+/// * `session` - The session to export with.
+/// * `domains` - The filter domains of the session.
+/// * `slice` - The slice to export.
+/// * `sheet` - The sheet to export.
+/// * `destination` - The file to write.
+/// * `bar_graph_destination` - Where to also export the bar graph, whose grade totals must match
+///   those of the exported sheet, or `None` to skip the check.
+async fn export_slice(
+    session: &TableauSession,
+    domains: &FilterDomains,
+    slice: &Slice,
+    sheet: &str,
+    destination: &Path,
+    bar_graph_destination: Option<&Path>,
+) -> Result<u64, NetworkError> {
+    session
+        .categorical_filter_indices(
+            &domains.filter("ACADEMIC_YEAR_SPAN")?.global_field_name,
+            &[slice.year_index],
+        )
+        .await?;
+    if let Some(department_indices) = &slice.department_indices {
+        session
+            .categorical_filter_indices(
+                &domains.filter("COURSE_PREFIX")?.global_field_name,
+                department_indices,
+            )
+            .await?;
+    }
+    for filter in &slice.filters {
+        session
+            .categorical_filter_values(
+                &domains.filter(&filter.field)?.global_field_name,
+                &filter.values,
+            )
+            .await?;
+    }
+    let bytes: u64 = session.export_csv(sheet, destination).await?;
+
+    if let Some(bar_graph_destination) = bar_graph_destination {
+        session
+            .export_csv(BAR_GRAPH_SHEET, bar_graph_destination)
+            .await?;
+        let mismatches: Vec<GradeTotalMismatch> =
+            compare_grade_totals(destination, bar_graph_destination)?;
+        if !mismatches.is_empty() {
+            return Err(NetworkError::GradeTotalsMismatch {
+                path: destination.display().to_string(),
+                mismatches,
+            });
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// The future of `export_slice_split`, boxed because parts of a split export are split again.
+type SplitExport<'a> =
+    std::pin::Pin<Box<dyn std::future::Future<Output = Result<u64, NetworkError>> + Send + 'a>>;
+
+/// Exports a slice like `export_slice`, re-exporting it split into smaller slices if the export
+/// fails or has fewer than `min_rows` rows, and concatenating the parts into `destination`.
+///
+/// Parts are split again if their own export fails, until they select a single department and
+/// semester. Errors that splitting can't help with, e.g. an expired session, are returned as is,
+/// after removing the parts exported so far.
+///
+/// # Arguments
+///
+/// * `session` - The session to export with.
+/// * `domains` - The filter domains of the session.
+/// * `slice` - The slice to export.
+/// * `options` - The sheet to export and whether to split exports.
+/// * `destination` - The file to write.
+/// * `bar_graph_destination` - Where to also export the bar graph, or `None` to skip the check.
+/// * `min_rows` - The fewest rows the export may have before it's split.
+/// * `pb` - The progress bar of the worker.
+#[allow(clippy::too_many_arguments)]
+fn export_slice_split<'a>(
+    session: &'a TableauSession,
+    domains: &'a FilterDomains,
+    slice: &'a Slice,
+    options: &'a DownloadOptions,
+    destination: &'a Path,
+    bar_graph_destination: Option<&'a Path>,
+    min_rows: u64,
+    pb: &'a progress::ProgressBar,
+) -> SplitExport<'a> {
+    Box::pin(async move {
+        let result: Result<u64, NetworkError> = export_slice(
+            session,
+            domains,
+            slice,
+            &options.sheet,
+            destination,
+            bar_graph_destination,
+        )
+        .await;
+        if !options.split_exports {
+            return result;
+        }
+        let reason: String = match &result {
+            Ok(_) => {
+                let rows: u64 = FileDigest::of_csv_file(destination, b',')
+                    .map_err(|err| NetworkError::file(destination, err))?
+                    .rows;
+                if rows >= min_rows {
+                    return result;
+                }
+                format!("only {} rows were exported", rows)
+            }
+            Err(err) if err.suggests_splitting() => err.to_string(),
+            Err(_) => return result,
+        };
+        let Some((field, parts)) = slice.split(domains) else {
+            return result;
+        };
+
+        pb.println(format!(
+            "Splitting {} by {}: {}",
+            slice.description, field, reason
+        ));
+        let mut part_paths: Vec<PathBuf> = Vec::new();
+        let mut bar_graph_part_paths: Vec<PathBuf> = Vec::new();
+        for (index, part) in parts.iter().enumerate() {
+            let part_destination: PathBuf = part_path(destination, index);
+            let bar_graph_part_destination: Option<PathBuf> =
+                bar_graph_destination.map(|path| part_path(path, index));
+            part_paths.push(part_destination.clone());
+            bar_graph_part_paths.extend(bar_graph_part_destination.clone());
+            pb.set_message(format!("Exporting CSV for {}", part.description));
+            let exported: Result<u64, NetworkError> = export_slice_split(
+                session,
+                domains,
+                part,
+                options,
+                &part_destination,
+                bar_graph_part_destination.as_deref(),
+                0,
+                pb,
+            )
+            .await;
+            if let Err(err) = exported {
+                remove_parts(&part_paths);
+                remove_parts(&bar_graph_part_paths);
+                return Err(err);
+            }
+        }
+        // The next slices only set the filters they narrow
+        session
+            .categorical_filter_all(&domains.filter(field)?.global_field_name)
+            .await?;
+
+        if let Some(bar_graph_destination) = bar_graph_destination {
+            concatenate_parts(&bar_graph_part_paths, bar_graph_destination)?;
+        }
+        concatenate_parts(&part_paths, destination)
+    })
+}
+
+type WorkerError = Box<dyn std::error::Error + Send + Sync>;
 
-    println!("[3/4] Set expanded values");
-    set_expanded_values(&session_id).await?;
+/// The state shared by the workers of a download.
+struct SharedDownload {
+    options: DownloadOptions,
+    /// The slices no worker has started yet
+    queue: Mutex<VecDeque<Slice>>,
+    state: Mutex<DownloadState>,
+    manifest: Mutex<Manifest>,
+    /// The progress of the whole download
+    total: progress::ProgressBar,
+}
+
+/// Downloads slices from the shared queue on its own VizQL session until the queue is empty.
+///
+/// # Arguments
+///
+/// * `session` - An already prepared session and its filter domains, or `None` to prepare a new one.
+/// * `config` - Where and how to open the sessions of this worker.
+/// * `shared` - The state shared by every worker.
+/// * `pb` - The progress bar of this worker.
+async fn download_slices(
+    session: Option<(TableauSession, FilterDomains)>,
+    config: SessionConfig,
+    shared: Arc<SharedDownload>,
+    pb: progress::ProgressBar,
+) -> Result<(), WorkerError> {
+    let (mut session, mut domains) = match session {
+        Some(prepared) => prepared,
+        None => prepare_session(&config, &pb).await?,
+    };
+
+    loop {
+        let Some(slice) = shared.queue.lock().unwrap().pop_front() else {
+            break;
+        };
+        // The slices were planned with the domains of another session, possibly since replaced
+        let slice: Slice = slice.relocate(&domains)?;
+
+        let destination: PathBuf =
+            Path::new(&shared.options.output_directory).join(&slice.file_name);
+        let bar_graph_destination: Option<PathBuf> = shared.options.cross_check.then(|| {
+            Path::new(&shared.options.output_directory)
+                .join(BAR_GRAPH_DIRECTORY)
+                .join(&slice.file_name)
+        });
+        shared
+            .state
+            .lock()
+            .unwrap()
+            .mark_incomplete(&slice.file_name)?;
+
+        pb.set_message(format!("Exporting CSV for {}", slice.description));
+        let bytes: u64 = match export_slice_split(
+            &session,
+            &domains,
+            &slice,
+            &shared.options,
+            &destination,
+            bar_graph_destination.as_deref(),
+            shared.options.min_rows,
+            &pb,
+        )
+        .await
+        {
+            Err(err) if err.is_session_expired() => {
+                shared
+                    .total
+                    .println("Session expired, bootstrapping a new session");
+                pb.set_style(worker_style());
+                (session, domains) = prepare_session(&config, &pb).await?;
+                export_slice_split(
+                    &session,
+                    &domains,
+                    &slice.relocate(&domains)?,
+                    &shared.options,
+                    &destination,
+                    bar_graph_destination.as_deref(),
+                    shared.options.min_rows,
+                    &pb,
+                )
+                .await?
+            }
+            result => result?,
+        };
+        pb.set_style(worker_style());
+
+        shared
+            .state
+            .lock()
+            .unwrap()
+            .mark_complete(&slice.file_name, bytes)?;
+
+        let entry: ManifestEntry = slice.manifest_entry(
+            FileDigest::of_csv_file(&destination, b',')?,
+            rfc3339_utc(SystemTime::now()),
+            &shared.options.session.grade_view,
+        );
+        let mut manifest = shared.manifest.lock().unwrap();
+        manifest.upsert(entry);
+        manifest.save(&shared.options.output_directory)?;
+        drop(manifest);
+
+        shared.total.inc(1);
+    }
+    pb.finish_and_clear();
+
+    Ok(())
+}
 
-    create_dir_all("out")?;
+/// Opens a session like `fetch_and_download_grade_distributions` and lists the categorical
+/// filters of the dashboard with their domains.
+///
+/// # Returns
+///
+/// * `Ok(Vec<DashboardFilter>)` - The filters, in dashboard order.
+/// * `Err(NetworkError)` - If preparing the session fails.
+pub async fn fetch_dashboard_filters(
+    config: &SessionConfig,
+) -> Result<Vec<DashboardFilter>, NetworkError> {
+    let pb: progress::ProgressBar = progress::ProgressBar::new_spinner();
+    pb.enable_steady_tick(Duration::from_millis(100));
+    let result = prepare_cached_session(config, &pb).await;
+    pb.finish_and_clear();
 
-    // 2010-2011 to 2022-2023
-    let years: usize = 13;
+    Ok(result?.1.filters)
+}
+
+/// Exports the grade distributions selected by `options` from the dashboard into CSV files of
+/// `options.output_directory`, one file per academic year or department, and describes them in
+/// its `manifest.json`.
+///
+/// # Returns
+///
+/// * `Ok(())` - If every file was exported.
+/// * `Err(Box<dyn std::error::Error>)` - A `NetworkError` if a session or an export fails, or
+///   another error if the selection or the output directory is invalid.
+///
+/// # Example
+///
+/// ```no_run
+/// use ut_grade_parser::network::{fetch_and_download_grade_distributions, DownloadOptions};
+///
+/// # async fn download() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut options: DownloadOptions = DownloadOptions::default();
+/// options.years.latest = true;
+/// options.departments = vec!["C S".to_string()];
+/// options.output_directory = "grade_distributions".to_string();
+///
+/// fetch_and_download_grade_distributions(&options).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn fetch_and_download_grade_distributions(
+    options: &DownloadOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let progress: progress::MultiProgress = progress::MultiProgress::new();
+    let new_worker_bar = |worker: usize| {
+        let pb = progress.add(progress::ProgressBar::new_spinner());
+        pb.set_style(worker_style());
+        pb.set_prefix(worker.to_string());
+        pb.enable_steady_tick(Duration::from_millis(100));
+        pb
+    };
 
-    println!("[4/4] Exporting CSVs");
-    let pb = indicatif::ProgressBar::new(years as u64);
-    pb.set_style(
-        indicatif::ProgressStyle::with_template(
+    let first_pb: progress::ProgressBar = new_worker_bar(1);
+    let (session, domains) = prepare_cached_session(&options.session, &first_pb).await?;
+    let available_years: Vec<u16> = domains
+        .academic_years
+        .iter()
+        .map(|(_, year)| *year)
+        .collect();
+    let year_indices: Vec<usize> = options.years.select(&available_years)?;
+    let slices: Vec<Slice> = plan_slices(options, &domains, &year_indices)?;
+
+    create_dir_all(&options.output_directory)?;
+    if options.cross_check {
+        create_dir_all(Path::new(&options.output_directory).join(BAR_GRAPH_DIRECTORY))?;
+    }
+    let state: DownloadState = DownloadState::load(&options.output_directory)?;
+    // Keep the entries of files downloaded by previous runs
+    let mut manifest: Manifest = Manifest::load(&options.output_directory)?.unwrap_or_default();
+    manifest.source = options.session.view_url();
+    manifest.tool_version = TOOL_VERSION.to_string();
+
+    let total = progress.insert(0, progress::ProgressBar::new(slices.len() as u64));
+    total.set_style(
+        progress::ProgressStyle::with_template(
             "[{elapsed_precise}] {bar:40} {pos:>7}/{len:7} {msg}",
         )
         .unwrap()
         .progress_chars("##-"),
     );
+    total.set_message("Exporting CSVs");
 
-    for i in 0..years {
-        pb.set_message(format!("Exporting CSV for {}-{}", i + 2010, i + 2011));
-        categorical_filter_indices(&session_id, "ACADEMIC_YEAR_SPAN", i).await?;
-        let csv = export_csv(&session_id).await?;
+    let mut queue: VecDeque<Slice> = VecDeque::new();
+    for slice in slices {
+        if options.resume && state.is_complete(&slice.file_name) {
+            total.println(format!("Skipping {}, already downloaded", slice.file_name));
+            total.inc(1);
+        } else {
+            queue.push_back(slice);
+        }
+    }
 
-        let file_name = format!("out/grade_distributions_{}-{}.csv", i + 2010, i + 2011);
-        let mut file = File::create(file_name)?;
-        file.write_all(&csv)?;
+    let jobs: usize = options.jobs.clamp(1, queue.len().max(1));
+    let shared: Arc<SharedDownload> = Arc::new(SharedDownload {
+        options: options.clone(),
+        queue: Mutex::new(queue),
+        state: Mutex::new(state),
+        manifest: Mutex::new(manifest),
+        total: total.clone(),
+    });
 
-        pb.inc(1);
+    let mut workers: tokio::task::JoinSet<Result<(), WorkerError>> = tokio::task::JoinSet::new();
+    workers.spawn(download_slices(
+        Some((session, domains)),
+        options.session.clone(),
+        shared.clone(),
+        first_pb,
+    ));
+    // Only the session of the first worker is cached, since a run reuses a single cached session
+    let other_config: SessionConfig = SessionConfig {
+        session_cache: None,
+        ..options.session.clone()
+    };
+    for worker in 2..=jobs {
+        workers.spawn(download_slices(
+            None,
+            other_config.clone(),
+            shared.clone(),
+            new_worker_bar(worker),
+        ));
     }
-    pb.finish_with_message("Exported all CSVs");
+
+    while let Some(result) = workers.join_next().await {
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => {
+                workers.abort_all();
+                return Err(err as Box<dyn std::error::Error>);
+            }
+            Err(err) => std::panic::resume_unwind(err.into_panic()),
+        }
+    }
+    total.finish_with_message("Exported all CSVs");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod mock_server;
+
+#[cfg(test)]
+mod tests {
+    use super::cross_check::grade_totals;
+    use super::mock_server::{
+        MockTableauServer, Request, ACADEMIC_YEARS, CONDENSED, COURSE_PREFIXES, SEMESTERS,
+    };
+    use super::schema::NameKind;
+    use super::traffic::REDACTED_COOKIE;
+    use super::*;
+
+    /// Creates an empty output directory for a test.
+    fn output_directory(name: &str) -> String {
+        let path: std::path::PathBuf =
+            std::env::temp_dir().join(format!("ut_grade_parser_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        path.to_str().unwrap().to_string()
+    }
+
+    fn download_options(server: &MockTableauServer, output_directory: &str) -> DownloadOptions {
+        DownloadOptions {
+            session: SessionConfig {
+                base_url: server.base_url.clone(),
+                retry: RetryPolicy {
+                    initial_backoff: Duration::from_millis(1),
+                    ..RetryPolicy::default()
+                },
+                ..SessionConfig::default()
+            },
+            output_directory: output_directory.to_string(),
+            ..DownloadOptions::default()
+        }
+    }
+
+    fn read_export(output_directory: &str, academic_year: &str) -> String {
+        std::fs::read_to_string(format!(
+            "{}/grade_distributions_{}.csv",
+            output_directory, academic_year
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_academic_years() {
+        assert_eq!(parse_academic_year("2022"), Ok(2022));
+        assert_eq!(parse_academic_year("2022-2023"), Ok(2022));
+        assert!(parse_academic_year("2022-2024").is_err());
+        assert!(parse_academic_year("Fall 2022").is_err());
+        assert_eq!(parse_academic_year_label("2022"), None);
+    }
+
+    #[test]
+    fn selects_academic_year_range() {
+        let available: [u16; 3] = [2020, 2021, 2022];
+        let range = |from, to, latest| AcademicYearRange { from, to, latest };
+
+        assert_eq!(
+            range(None, None, false).select(&available),
+            Ok(vec![0, 1, 2])
+        );
+        assert_eq!(
+            range(Some(2021), None, false).select(&available),
+            Ok(vec![1, 2])
+        );
+        assert_eq!(
+            range(None, Some(2020), false).select(&available),
+            Ok(vec![0])
+        );
+        assert_eq!(range(None, None, true).select(&available), Ok(vec![2]));
+        assert!(range(Some(2022), Some(2021), false)
+            .select(&available)
+            .is_err());
+        assert!(range(Some(2030), None, false).select(&available).is_err());
+    }
+
+    #[test]
+    fn reads_length_prefixed_bootstrap_response() {
+        let documents: Vec<serde_json::Value> =
+            parse_vizql_response(r#"7;{"a":1}7;{"b":2}"#).unwrap();
+        assert_eq!(
+            documents,
+            vec![serde_json::json!({"a": 1}), serde_json::json!({"b": 2})]
+        );
+        assert_eq!(
+            parse_vizql_response(r#"{"c":3}"#).unwrap(),
+            vec![serde_json::json!({"c": 3})]
+        );
+    }
+
+    #[test]
+    fn reads_documents_with_non_ascii_text() {
+        // The lengths count characters, which are fewer than the bytes of `Économie`
+        let documents: Vec<serde_json::Value> =
+            parse_vizql_response(r#"22;{"label":"Économie ✓"}8;{"b":[]}"#).unwrap();
+        assert_eq!(
+            documents,
+            vec![
+                serde_json::json!({"label": "Économie ✓"}),
+                serde_json::json!({"b": []})
+            ]
+        );
+        assert!(matches!(
+            parse_vizql_response(r#"23;{"label":"Économie"#),
+            Err(NetworkError::UnexpectedJson { .. })
+        ));
+        assert!(parse_vizql_response(r#"7;{"a":1}trailing"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn downloads_every_academic_year() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("every_year");
+
+        fetch_and_download_grade_distributions(&download_options(&server, &output_directory))
+            .await
+            .unwrap();
+
+        for academic_year in ACADEMIC_YEARS {
+            assert_eq!(
+                read_export(&output_directory, academic_year),
+                MockTableauServer::crosstab_csv(academic_year, &COURSE_PREFIXES)
+            );
+        }
+        let parameter = server
+            .requests()
+            .into_iter()
+            .find(|request| request.path.ends_with("/set-parameter-value"))
+            .unwrap();
+        assert_eq!(parameter.fields["valueString"], "Expanded");
+    }
+
+    #[tokio::test]
+    async fn downloads_selected_academic_years() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("selected_years");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        let files: Vec<String> = std::fs::read_dir(&output_directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|file_name| file_name.ends_with(".csv"))
+            .collect();
+        assert_eq!(files, vec!["grade_distributions_2022-2023.csv"]);
+    }
+
+    #[tokio::test]
+    async fn retries_transient_failures() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("transient_failures");
+        server.fail_next_requests(2);
+
+        fetch_and_download_grade_distributions(&download_options(&server, &output_directory))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            read_export(&output_directory, "2020-2021"),
+            MockTableauServer::crosstab_csv("2020-2021", &COURSE_PREFIXES)
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_stalled_requests_after_the_read_timeout() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("read_timeout");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+        options.session.transport.read_timeout = Duration::from_millis(100);
+        options.session.transport.user_agent = "grade-mirror/1.0".to_string();
+
+        server.stall_next_requests(1);
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            read_export(&output_directory, "2022-2023"),
+            MockTableauServer::crosstab_csv("2022-2023", &COURSE_PREFIXES)
+        );
+        let requests: Vec<Request> = server.requests();
+        assert!(requests[0].path.starts_with("/views/"));
+        assert!(requests[1].path.starts_with("/views/"));
+        assert!(requests
+            .iter()
+            .all(|request| request.headers["user-agent"] == "grade-mirror/1.0"));
+    }
+
+    #[tokio::test]
+    async fn reports_read_timeouts() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("read_timeout_exhausted");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.session.retry.max_retries = 0;
+        options.session.transport.read_timeout = Duration::from_millis(100);
+
+        server.stall_next_requests(1);
+        let err = fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap_err();
+
+        let err: &NetworkError = err.downcast_ref::<NetworkError>().unwrap();
+        assert!(matches!(err, NetworkError::Timeout { .. }), "{}", err);
+        assert_eq!(err.exit_code(), 69);
+    }
+
+    #[tokio::test]
+    async fn retries_truncated_downloads() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("truncated_download");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+
+        server.truncate_next_downloads(1);
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            read_export(&output_directory, "2022-2023"),
+            MockTableauServer::crosstab_csv("2022-2023", &COURSE_PREFIXES)
+        );
+        let downloads: usize = server
+            .requests()
+            .iter()
+            .filter(|request| request.path.contains("/tempfile/"))
+            .count();
+        assert_eq!(downloads, 2);
+        let partial_files: usize = std::fs::read_dir(&output_directory)
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .path()
+                    .extension()
+                    .is_some_and(|extension| extension == "part")
+            })
+            .count();
+        assert_eq!(partial_files, 0);
+    }
+
+    #[tokio::test]
+    async fn keeps_no_file_when_a_download_is_cut_short() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("cut_short_download");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+        options.session.retry.max_retries = 0;
+        options.split_exports = false;
+
+        server.truncate_next_downloads(1);
+        let err = fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap_err();
+
+        assert!(err.downcast_ref::<NetworkError>().is_some());
+        let files: Vec<String> = std::fs::read_dir(&output_directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|file_name| file_name.contains("grade_distributions"))
+            .collect();
+        assert!(files.is_empty(), "{:?}", files);
+    }
+
+    #[tokio::test]
+    async fn reports_exhausted_retries() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("exhausted_retries");
+        server.fail_next_requests(10);
+
+        let err: Box<dyn std::error::Error> =
+            fetch_and_download_grade_distributions(&download_options(&server, &output_directory))
+                .await
+                .unwrap_err();
+
+        let err: &NetworkError = err.downcast_ref().unwrap();
+        assert!(matches!(err, NetworkError::Status { status, .. } if status.as_u16() == 503));
+    }
+
+    #[tokio::test]
+    async fn bootstraps_a_new_session_when_expired() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("expired_session");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.cross_check = true;
+        // The session expires once the crosstab of the first academic year has been downloaded,
+        // before its bar graph is exported
+        server.expire_sessions_after_downloads(1);
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        for academic_year in ACADEMIC_YEARS {
+            assert_eq!(
+                read_export(&output_directory, academic_year),
+                MockTableauServer::crosstab_csv(academic_year, &COURSE_PREFIXES)
+            );
+        }
+        let bootstraps: usize = server
+            .requests()
+            .iter()
+            .filter(|request| request.path.contains("/bootstrapSession/"))
+            .count();
+        assert_eq!(bootstraps, 2);
+        let state: DownloadState = DownloadState::load(&output_directory).unwrap();
+        assert!(ACADEMIC_YEARS.iter().all(|academic_year| {
+            state.is_complete(&format!("grade_distributions_{}.csv", academic_year))
+        }));
+    }
+
+    #[tokio::test]
+    async fn relocates_the_academic_year_in_a_new_session() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("relocated_academic_year");
+        let options: DownloadOptions = download_options(&server, &output_directory);
+        // The replacement session lists a new academic year first, shifting the indices of the
+        // others
+        server.expire_sessions_after_downloads(1);
+        server.publish_academic_year(1, "2023-2024");
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        for academic_year in ACADEMIC_YEARS {
+            assert_eq!(
+                read_export(&output_directory, academic_year),
+                MockTableauServer::crosstab_csv(academic_year, &COURSE_PREFIXES)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn downloads_selected_departments() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("selected_departments");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+        options.departments = vec!["c s".to_string()];
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            read_export(&output_directory, "2022-2023_C_S"),
+            MockTableauServer::crosstab_csv("2022-2023", &["C S"])
+        );
+    }
+
+    #[tokio::test]
+    async fn downloads_the_selected_grade_view() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("grade_view");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+        options.session.grade_view = CONDENSED.to_string();
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            read_export(&output_directory, "2022-2023"),
+            MockTableauServer::condensed_csv(&MockTableauServer::crosstab_csv(
+                "2022-2023",
+                &COURSE_PREFIXES
+            ))
+        );
+        let parameter: Request = server
+            .requests()
+            .into_iter()
+            .find(|request| request.path.ends_with("/set-parameter-value"))
+            .unwrap();
+        assert_eq!(parameter.fields["valueString"], CONDENSED);
+        let manifest: Manifest = Manifest::load(&output_directory).unwrap().unwrap();
+        assert_eq!(manifest.files[0].parameters[PARAMETER_1], CONDENSED);
+    }
+
+    #[tokio::test]
+    async fn splits_failed_exports_by_department() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let unsplittable_directory: String = output_directory("split_failed_exports_limit");
+        let output_directory: String = output_directory("split_failed_exports");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+        options.session.retry.max_retries = 0;
+
+        server.limit_export_rows(2);
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            read_export(&output_directory, "2022-2023"),
+            MockTableauServer::crosstab_csv("2022-2023", &COURSE_PREFIXES)
+        );
+        let files: Vec<String> = std::fs::read_dir(&output_directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|file_name| file_name.contains(".split") || file_name.ends_with(".part"))
+            .collect();
+        assert!(files.is_empty(), "{:?}", files);
+        let last_filter: Request = server
+            .requests()
+            .into_iter()
+            .rfind(|request| request.path.contains("/categorical-filter"))
+            .unwrap();
+        assert_eq!(last_filter.fields["filterUpdateType"], "filter-all");
+
+        // A single department and semester can't be split any further
+        options.output_directory = unsplittable_directory;
+        server.limit_export_rows(1);
+        let err: Box<dyn std::error::Error> = fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<NetworkError>().unwrap().is_transient());
+    }
+
+    #[tokio::test]
+    async fn removes_the_parts_of_a_failed_split_export() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("split_export_failure");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.session.retry.max_retries = 0;
+        create_dir_all(&output_directory).unwrap();
+        let (session, domains) =
+            prepare_session(&options.session, &progress::ProgressBar::hidden())
+                .await
+                .unwrap();
+
+        // The export is split by department, and the session expires once the first department
+        // has been downloaded
+        server.limit_export_rows(2);
+        server.expire_sessions_after_downloads(1);
+        let slice: Slice = Slice {
+            year_index: 2,
+            department_indices: None,
+            academic_year: "2022-2023".to_string(),
+            departments: Vec::new(),
+            filters: Vec::new(),
+            file_name: "grade_distributions_2022-2023.csv".to_string(),
+            description: "2022-2023".to_string(),
+        };
+        let destination: PathBuf = Path::new(&output_directory).join(&slice.file_name);
+        let err: NetworkError = export_slice_split(
+            &session,
+            &domains,
+            &slice,
+            &options,
+            &destination,
+            None,
+            options.min_rows,
+            &progress::ProgressBar::hidden(),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(err.is_session_expired());
+        let files: Vec<String> = std::fs::read_dir(&output_directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert!(files.is_empty(), "{:?}", files);
+    }
+
+    #[tokio::test]
+    async fn splits_short_exports() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let cross_check_directory: String = output_directory("split_short_exports_cross_check");
+        let output_directory: String = output_directory("split_short_exports");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+        options.min_rows = 4;
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        let crosstab: String = MockTableauServer::crosstab_csv("2022-2023", &COURSE_PREFIXES);
+        assert_eq!(read_export(&output_directory, "2022-2023"), crosstab);
+        let exports: usize = server
+            .requests()
+            .iter()
+            .filter(|request| request.path.ends_with("/export-crosstab-to-csvserver"))
+            .count();
+        assert_eq!(exports, 1 + COURSE_PREFIXES.len());
+
+        // An export missing a row is caught by the bar graph and exported again split
+        let output_directory: String = cross_check_directory;
+        options.output_directory = output_directory.clone();
+        options.min_rows = 1;
+        options.cross_check = true;
+        server.shorten_next_exports(1);
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+        assert_eq!(read_export(&output_directory, "2022-2023"), crosstab);
+        assert_eq!(
+            grade_totals(
+                &Path::new(&output_directory)
+                    .join(BAR_GRAPH_DIRECTORY)
+                    .join("grade_distributions_2022-2023.csv")
+            )
+            .unwrap(),
+            grade_totals(&Path::new(&output_directory).join("grade_distributions_2022-2023.csv"))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn splits_slices_by_department_then_semester() {
+        let filter = |field: &str, domain: &[&str]| DashboardFilter {
+            field: field.to_string(),
+            global_field_name: format!("[sqlproxy].[none:{}:nk]", field),
+            domain: domain.iter().map(|value| value.to_string()).collect(),
+        };
+        let domains: FilterDomains = FilterDomains {
+            academic_years: vec![("2022-2023".to_string(), 2022)],
+            course_prefixes: COURSE_PREFIXES.iter().map(|p| p.to_string()).collect(),
+            filters: vec![
+                filter("COURSE_PREFIX", &COURSE_PREFIXES),
+                filter(SEMESTER_FIELD, &SEMESTERS),
+            ],
+        };
+        let slice: Slice = Slice {
+            year_index: 0,
+            department_indices: None,
+            academic_year: "2022-2023".to_string(),
+            departments: Vec::new(),
+            filters: Vec::new(),
+            file_name: "grade_distributions_2022-2023.csv".to_string(),
+            description: "2022-2023".to_string(),
+        };
+
+        let (field, parts) = slice.split(&domains).unwrap();
+        assert_eq!(field, "COURSE_PREFIX");
+        assert_eq!(
+            parts
+                .iter()
+                .map(|part| part.department_indices.clone())
+                .collect::<Vec<_>>(),
+            vec![Some(vec![0]), Some(vec![1])]
+        );
+        assert_eq!(parts[1].description, "2022-2023 M");
+
+        let (field, parts) = parts[1].split(&domains).unwrap();
+        assert_eq!(field, SEMESTER_FIELD);
+        assert_eq!(
+            parts
+                .iter()
+                .map(|part| part.filters[0].values.clone())
+                .collect::<Vec<_>>(),
+            SEMESTERS.map(|semester| vec![semester.to_string()])
+        );
+        assert!(parts[0].split(&domains).is_none());
+    }
+
+    #[tokio::test]
+    async fn writes_a_download_manifest() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("manifest");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+        options.departments = vec!["M".to_string()];
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        let manifest: Manifest = Manifest::load(&output_directory).unwrap().unwrap();
+        assert_eq!(manifest.source, options.session.view_url());
+        assert_eq!(manifest.files.len(), 1);
+
+        let entry: &ManifestEntry = manifest
+            .entry("grade_distributions_2022-2023_M.csv")
+            .unwrap();
+        let csv: String = MockTableauServer::crosstab_csv("2022-2023", &["M"]);
+        let digest: FileDigest =
+            FileDigest::of_csv_file(&Path::new(&output_directory).join(&entry.file_name), b',')
+                .unwrap();
+        assert_eq!(read_export(&output_directory, "2022-2023_M"), csv);
+        assert_eq!(entry.academic_year, "2022-2023");
+        assert_eq!(entry.filters["ACADEMIC_YEAR_SPAN"], vec!["2022-2023"]);
+        assert_eq!(entry.filters["COURSE_PREFIX"], vec!["M"]);
+        assert_eq!(entry.parameters[PARAMETER_1], "Expanded");
+        assert_eq!(entry.bytes, csv.len() as u64);
+        assert_eq!(entry.sha256, digest.sha256);
+        assert_eq!(entry.rows, (csv.lines().count() - 1) as u64);
+        assert_eq!(entry.tool_version, TOOL_VERSION);
+    }
+
+    #[tokio::test]
+    async fn partitions_by_department() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("partition_department");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.from = Some(2021);
+        options.partition = Partition::Department;
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        for academic_year in &ACADEMIC_YEARS[1..] {
+            assert_eq!(
+                read_export(&output_directory, &format!("{}_C_S", academic_year)),
+                MockTableauServer::crosstab_csv(academic_year, &["C S"])
+            );
+            assert_eq!(
+                read_export(&output_directory, &format!("{}_M", academic_year)),
+                MockTableauServer::crosstab_csv(academic_year, &["M"])
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn downloads_concurrently_on_separate_sessions() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("concurrent");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.partition = Partition::Department;
+        options.jobs = 3;
+        options.session.rate_limiter = Some(Arc::new(RateLimiter::new(1000.0).unwrap()));
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        for academic_year in ACADEMIC_YEARS {
+            for course_prefix in COURSE_PREFIXES {
+                assert_eq!(
+                    read_export(
+                        &output_directory,
+                        &format!("{}{}", academic_year, file_name_suffix(&[course_prefix]))
+                    ),
+                    MockTableauServer::crosstab_csv(academic_year, &[course_prefix])
+                );
+            }
+        }
+        let sessions: usize = server
+            .requests()
+            .iter()
+            .filter(|request| request.path.starts_with("/views/"))
+            .count();
+        assert_eq!(sessions, 3);
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_departments() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("unknown_departments");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.departments = vec!["C S".to_string(), "XYZ".to_string()];
+
+        let err: Box<dyn std::error::Error> = fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("XYZ"));
+    }
+
+    #[tokio::test]
+    async fn reports_dashboard_schema_drift() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("schema_drift");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.session.diagnostics_directory = Path::new(&output_directory).join("diagnostics");
+
+        server.rename_in_bootstrap(SEMESTER_FIELD, "Calculation_3161245480939225090");
+        server.rename_in_bootstrap(PARAMETER_1, "[Parameters].[Grade view]");
+        let err = fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap_err();
+
+        let Some(NetworkError::SchemaDrift { missing, .. }) = err.downcast_ref::<NetworkError>()
+        else {
+            panic!("Unexpected error: {}", err);
+        };
+        assert_eq!(
+            missing,
+            &vec![
+                MissingName {
+                    kind: NameKind::Filter,
+                    expected: SEMESTER_FIELD.to_string(),
+                    suggestions: vec!["Calculation_3161245480939225090".to_string()],
+                },
+                MissingName {
+                    kind: NameKind::Parameter,
+                    expected: PARAMETER_1.to_string(),
+                    suggestions: vec!["[Parameters].[Grade view]".to_string()],
+                },
+            ]
+        );
+        let diagnostics: PathBuf = options.session.diagnostics_directory;
+        let bootstrap: String =
+            std::fs::read_to_string(diagnostics.join("bootstrapSession.txt")).unwrap();
+        assert!(bootstrap.contains("Calculation_3161245480939225090"));
+        let ts_config: String =
+            std::fs::read_to_string(diagnostics.join("tsConfigContainer.json")).unwrap();
+        assert_eq!(
+            json_str_at(&ts_config, "/sessionid").unwrap(),
+            REDACTED_SESSION_ID
+        );
+        assert!(!server
+            .requests()
+            .iter()
+            .any(|request| request.path.contains("/commands/")));
+    }
+
+    #[test]
+    fn quotes_every_suggestion_of_missing_names() {
+        let mut missing: MissingName = MissingName {
+            kind: NameKind::Sheet,
+            expected: SHEET_ID.to_string(),
+            suggestions: vec!["External dashboard-crosstab".to_string()],
+        };
+        assert_eq!(
+            missing.to_string(),
+            r#"sheet "External dashboard-Crosstab" not found (did you mean "External dashboard-crosstab"?)"#
+        );
+        missing
+            .suggestions
+            .push("External dashboard-Crosstabs".to_string());
+        assert_eq!(
+            missing.to_string(),
+            r#"sheet "External dashboard-Crosstab" not found (did you mean "External dashboard-crosstab", "External dashboard-Crosstabs"?)"#
+        );
+        missing.suggestions.clear();
+        assert_eq!(
+            missing.to_string(),
+            r#"sheet "External dashboard-Crosstab" not found"#
+        );
+    }
+
+    #[tokio::test]
+    async fn lists_dashboard_filters() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let options: DownloadOptions = download_options(&server, &output_directory("list"));
+
+        let filters: Vec<DashboardFilter> =
+            fetch_dashboard_filters(&options.session).await.unwrap();
+
+        let domains: Vec<(&str, Vec<&str>)> = filters
+            .iter()
+            .map(|filter| {
+                (
+                    filter.field.as_str(),
+                    filter.domain.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            domains,
+            vec![
+                ("ACADEMIC_YEAR_SPAN", ACADEMIC_YEARS.to_vec()),
+                ("COURSE_PREFIX", COURSE_PREFIXES.to_vec()),
+                (SEMESTER_FIELD, SEMESTERS.to_vec()),
+            ]
+        );
+        assert_eq!(
+            filters[1].global_field_name,
+            "[sqlproxy.0mockdatasource0].[none:COURSE_PREFIX:nk]"
+        );
+    }
+
+    #[tokio::test]
+    async fn downloads_filtered_values() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("filtered_values");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+        options.filters =
+            vec![parse_field_filter("calculation_3161245480939225089=spring").unwrap()];
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            read_export(&output_directory, "2022-2023_Spring"),
+            MockTableauServer::filtered_crosstab_csv("2022-2023", &COURSE_PREFIXES, &["Spring"])
+        );
+        let filter: Request = server
+            .requests()
+            .into_iter()
+            .find(|request| {
+                request.fields.get("filterUpdateType").map(String::as_str) == Some("filter-replace")
+                    && request.fields.contains_key("filterValues")
+            })
+            .unwrap();
+        assert_eq!(
+            filter.fields["globalFieldName"],
+            "[sqlproxy.0mockdatasource0].[none:Calculation_3161245480939225089:nk]"
+        );
+        assert_eq!(filter.fields["filterValues"], r#"["Spring"]"#);
+        let manifest: Manifest = Manifest::load(&output_directory).unwrap().unwrap();
+        assert_eq!(
+            manifest.files[0].filters[SEMESTER_FIELD],
+            vec!["Spring".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_filters() {
+        assert_eq!(
+            parse_field_filter("COLLEGE=Natural Sciences, Engineering"),
+            Ok(FieldFilter {
+                field: "COLLEGE".to_string(),
+                values: vec!["Natural Sciences".to_string(), "Engineering".to_string()],
+            })
+        );
+        assert!(parse_field_filter("COLLEGE").is_err());
+        assert!(parse_field_filter("COLLEGE=").is_err());
+
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("unknown_filters");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        for (filter, message) in [
+            ("COLLEGE=Engineering", "Unknown filter field"),
+            (
+                "Calculation_3161245480939225089=Winter",
+                r#"Unknown Calculation_3161245480939225089 values ["Winter"]"#,
+            ),
+            ("COURSE_PREFIX=M", "--department"),
+        ] {
+            options.filters = vec![parse_field_filter(filter).unwrap()];
+            let err: Box<dyn std::error::Error> = fetch_and_download_grade_distributions(&options)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains(message), "{}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn exports_sheets_by_name() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("sheets_by_name");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+        options.sheet = BAR_GRAPH_SHEET.to_string();
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            read_export(&output_directory, "2022-2023"),
+            MockTableauServer::bar_graph_csv(&MockTableauServer::crosstab_csv(
+                "2022-2023",
+                &COURSE_PREFIXES
+            ))
+        );
+        assert!(server.requests().iter().any(|request| {
+            request.fields.get("sheetdocId").map(String::as_str) == Some("bar-graph-doc")
+        }));
+
+        options.sheet = "External dashboard-Map".to_string();
+        let err: Box<dyn std::error::Error> = fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The export dialog has no sheet \"External dashboard-Map\" \
+             (available: External dashboard-Crosstab, External dashboard-bar graph)"
+        );
+    }
+
+    #[tokio::test]
+    async fn cross_checks_exports_against_the_bar_graph() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let mismatch_directory: String = output_directory("cross_check_mismatch");
+        let output_directory: String = output_directory("cross_check");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+        options.cross_check = true;
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        let crosstab: String = MockTableauServer::crosstab_csv("2022-2023", &COURSE_PREFIXES);
+        assert_eq!(read_export(&output_directory, "2022-2023"), crosstab);
+        assert_eq!(
+            std::fs::read_to_string(
+                Path::new(&output_directory)
+                    .join(BAR_GRAPH_DIRECTORY)
+                    .join("grade_distributions_2022-2023.csv")
+            )
+            .unwrap(),
+            MockTableauServer::bar_graph_csv(&crosstab)
+        );
+
+        options.output_directory = mismatch_directory.clone();
+        options.split_exports = false;
+        server.shorten_next_exports(1);
+        let err: Box<dyn std::error::Error> = fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap_err();
+        let err: &NetworkError = err.downcast_ref::<NetworkError>().unwrap();
+        assert!(matches!(
+            err,
+            NetworkError::GradeTotalsMismatch { mismatches, .. }
+                if mismatches == &[GradeTotalMismatch {
+                    grade: "A-".to_string(),
+                    crosstab: 0,
+                    bar_graph: 7,
+                }]
+        ));
+        assert_eq!(err.exit_code(), 65);
+        assert!(!DownloadState::load(&mismatch_directory)
+            .unwrap()
+            .is_complete("grade_distributions_2022-2023.csv"));
+    }
+
+    #[tokio::test]
+    async fn reuses_cached_sessions() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("session_cache");
+        let cache_path: PathBuf = Path::new(&output_directory).join("session.json");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+        options.departments = vec!["M".to_string()];
+        options.session.session_cache = Some(cache_path.clone());
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        let cached: CachedSession = CachedSession::load(&cache_path).unwrap().unwrap();
+        assert_eq!(cached.session_id, "MOCK-SESSION-0");
+        assert_eq!(cached.cookies.as_deref(), Some("tableau_locale=en"));
+        assert_eq!(
+            cached.narrowed_filters,
+            ["[sqlproxy.0mockdatasource0].[none:COURSE_PREFIX:nk]".to_string()].into()
+        );
+
+        // The next run reuses the session, after selecting every department again
+        let previous_requests: usize = server.requests().len();
+        options.departments.clear();
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        let requests: Vec<Request> = server.requests().split_off(previous_requests);
+        assert!(requests
+            .iter()
+            .all(|request| request.path.contains("/sessions/MOCK-SESSION-0/")));
+        assert_eq!(
+            requests[0].headers.get("cookie").map(String::as_str),
+            Some("tableau_locale=en")
+        );
+        assert_eq!(
+            read_export(&output_directory, "2022-2023"),
+            MockTableauServer::crosstab_csv("2022-2023", &COURSE_PREFIXES)
+        );
+
+        // An expired session is replaced by a new one
+        server.expire_sessions();
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        let cached: CachedSession = CachedSession::load(&cache_path).unwrap().unwrap();
+        assert_eq!(cached.session_id, "MOCK-SESSION-1");
+        assert!(cached.narrowed_filters.is_empty());
+    }
+
+    #[tokio::test]
+    async fn caches_only_the_first_session_of_concurrent_downloads() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("concurrent_session_cache");
+        let cache_path: PathBuf = Path::new(&output_directory).join("session.json");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+        options.partition = Partition::Department;
+        options.jobs = 2;
+        options.session.session_cache = Some(cache_path.clone());
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        let cached: CachedSession = CachedSession::load(&cache_path).unwrap().unwrap();
+        assert_eq!(cached.session_id, "MOCK-SESSION-0");
+        assert_eq!(
+            cached.narrowed_filters,
+            ["[sqlproxy.0mockdatasource0].[none:COURSE_PREFIX:nk]".to_string()].into()
+        );
+    }
+
+    #[tokio::test]
+    async fn writes_a_har_file_of_the_session() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("har");
+        let har_path: PathBuf = PathBuf::from(format!("{}.har", output_directory));
+        let tracer: Arc<Tracer> = Arc::new(Tracer::new(Some(har_path.clone())));
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        options.years.latest = true;
+        options.session.tracer = Some(tracer.clone());
+
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+        assert_eq!(tracer.save_har().unwrap(), Some(har_path.as_path()));
+
+        let csv: String = MockTableauServer::crosstab_csv("2022-2023", &COURSE_PREFIXES);
+        assert_eq!(read_export(&output_directory, "2022-2023"), csv);
+        let har: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&har_path).unwrap()).unwrap();
+        let entries: &Vec<serde_json::Value> = har["log"]["entries"].as_array().unwrap();
+        assert_eq!(har["log"]["version"], "1.2");
+        // The cookies set by the server are masked
+        assert!(entries[0]["response"]["headers"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!({"name": "set-cookie", "value": REDACTED_COOKIE})));
+        assert_eq!(entries.len(), server.requests().len());
+        let parameter: &serde_json::Value = entries
+            .iter()
+            .find(|entry| {
+                entry["request"]["url"]
+                    .as_str()
+                    .unwrap()
+                    .ends_with("/commands/tabdoc/set-parameter-value")
+            })
+            .unwrap();
+        assert_eq!(parameter["request"]["method"], "POST");
+        assert!(parameter["request"]["postData"]["params"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!({"name": "valueString", "value": "Expanded"})));
+        assert_eq!(parameter["response"]["status"], 200);
+        let download: &serde_json::Value = entries.last().unwrap();
+        assert_eq!(download["request"]["queryString"][0]["name"], "key");
+        assert_eq!(download["response"]["content"]["mimeType"], "text/csv");
+        assert_eq!(download["response"]["content"]["size"], csv.len());
+        assert!(download["response"]["content"].get("text").is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_request_rates() {
+        for requests_per_second in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e-300] {
+            assert!(matches!(
+                RateLimiter::new(requests_per_second),
+                Err(NetworkError::Config(_))
+            ));
+        }
+        assert!(RateLimiter::new(0.5).is_ok());
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_traffic() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let recording_directory: String = output_directory("recording");
+        let recorded_output_directory: String = output_directory("recorded");
+        let mut options: DownloadOptions = download_options(&server, &recorded_output_directory);
+        options.session.traffic = Some(Arc::new(
+            Traffic::open(&TrafficMode::Record(recording_directory.clone().into())).unwrap(),
+        ));
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        for entry in std::fs::read_dir(&recording_directory).unwrap() {
+            let recording: String = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            assert!(!recording.contains("MOCK-SESSION"));
+        }
+        let exchange: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(format!("{}/0001.json", recording_directory)).unwrap(),
+        )
+        .unwrap();
+        assert!(exchange["response"]["headers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|header| header[0] == "content-type"));
+        assert_eq!(exchange["response"]["body_file"], "0001.body");
+
+        let replayed_output_directory: String = output_directory("replayed");
+        let mut options: DownloadOptions = download_options(&server, &replayed_output_directory);
+        // Nothing listens on the discard port, so any request reaching the network fails
+        options.session.base_url = "http://127.0.0.1:9".to_string();
+        options.session.traffic = Some(Arc::new(
+            Traffic::open(&TrafficMode::Replay(recording_directory.into())).unwrap(),
+        ));
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        for academic_year in ACADEMIC_YEARS {
+            assert_eq!(
+                read_export(&replayed_output_directory, academic_year),
+                read_export(&recorded_output_directory, academic_year)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn resumes_completed_downloads() {
+        let server: MockTableauServer = MockTableauServer::start().await;
+        let output_directory: String = output_directory("resume");
+        let mut options: DownloadOptions = download_options(&server, &output_directory);
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        // A truncated file isn't considered complete
+        std::fs::write(
+            format!("{}/grade_distributions_2021-2022.csv", output_directory),
+            "Semester",
+        )
+        .unwrap();
+        let requests_before: usize = server.requests().len();
+
+        options.resume = true;
+        fetch_and_download_grade_distributions(&options)
+            .await
+            .unwrap();
+
+        let exports: usize = server.requests()[requests_before..]
+            .iter()
+            .filter(|request| request.path.contains("/tempfile/"))
+            .count();
+        assert_eq!(exports, 1);
+        assert_eq!(
+            read_export(&output_directory, "2021-2022"),
+            MockTableauServer::crosstab_csv("2021-2022", &COURSE_PREFIXES)
+        );
+    }
+}
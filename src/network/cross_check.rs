@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use super::error::NetworkError;
+
+/// A grade whose total differs between the crosstab and the bar graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GradeTotalMismatch {
+    pub grade: String,
+    /// The sum of the counts of the grade in the crosstab export
+    pub crosstab: u64,
+    /// The count of the grade in the bar graph export
+    pub bar_graph: u64,
+}
+
+impl fmt::Display for GradeTotalMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "grade {}: {} in the crosstab, {} in the bar graph",
+            self.grade, self.crosstab, self.bar_graph
+        )
+    }
+}
+
+/// Sums the counts of each letter grade in an exported CSV file.
+///
+/// The grade column is the first one whose header mentions a grade but not a count, e.g.
+/// `Letter Grade`, and the count column is the first one whose header mentions a count, e.g.
+/// `Count of letter grade`. Counts may have thousands separators, e.g. `1,024`.
+///
+/// # Returns
+///
+/// * `Ok(BTreeMap<String, u64>)` - The total count of each grade.
+/// * `Err(NetworkError::InvalidExport)` - If the file can't be read or lacks either column.
+pub fn grade_totals(path: &Path) -> Result<BTreeMap<String, u64>, NetworkError> {
+    let invalid = |reason: String| NetworkError::InvalidExport {
+        path: path.display().to_string(),
+        reason,
+    };
+
+    let mut reader: csv::Reader<std::fs::File> = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .map_err(|err| invalid(err.to_string()))?;
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|err| invalid(err.to_string()))?
+        .iter()
+        .map(|header| header.trim_start_matches('\u{feff}').to_lowercase())
+        .collect();
+    let grade_column: usize = headers
+        .iter()
+        .position(|header| header.contains("grade") && !header.contains("count"))
+        .ok_or_else(|| invalid("no letter grade column".to_string()))?;
+    let count_column: usize = headers
+        .iter()
+        .position(|header| header.contains("count"))
+        .ok_or_else(|| invalid("no count column".to_string()))?;
+
+    let mut totals: BTreeMap<String, u64> = BTreeMap::new();
+    for (row, record) in reader.records().enumerate() {
+        let record: csv::StringRecord = record.map_err(|err| invalid(err.to_string()))?;
+        let grade: &str = record.get(grade_column).unwrap_or_default().trim();
+        let count: &str = record.get(count_column).unwrap_or_default().trim();
+        if grade.is_empty() {
+            continue;
+        }
+        let count: u64 = if count.is_empty() {
+            0
+        } else {
+            count
+                .replace(',', "")
+                .parse()
+                .map_err(|_| invalid(format!("invalid count {:?} on row {}", count, row + 1)))?
+        };
+        *totals.entry(grade.to_string()).or_default() += count;
+    }
+
+    Ok(totals)
+}
+
+/// Compares the per-grade totals of a crosstab export with those of a bar graph export made
+/// with the same filters.
+///
+/// # Returns
+///
+/// * `Ok(Vec<GradeTotalMismatch>)` - The grades whose totals differ, or an empty list if all match.
+/// * `Err(NetworkError::InvalidExport)` - If either file can't be read.
+pub fn compare_grade_totals(
+    crosstab: &Path,
+    bar_graph: &Path,
+) -> Result<Vec<GradeTotalMismatch>, NetworkError> {
+    let crosstab_totals: BTreeMap<String, u64> = grade_totals(crosstab)?;
+    let bar_graph_totals: BTreeMap<String, u64> = grade_totals(bar_graph)?;
+
+    let mut grades: Vec<&String> = crosstab_totals
+        .keys()
+        .chain(bar_graph_totals.keys())
+        .collect();
+    grades.sort();
+    grades.dedup();
+
+    Ok(grades
+        .into_iter()
+        .map(|grade| GradeTotalMismatch {
+            grade: grade.clone(),
+            crosstab: crosstab_totals.get(grade).copied().unwrap_or(0),
+            bar_graph: bar_graph_totals.get(grade).copied().unwrap_or(0),
+        })
+        .filter(|totals| totals.crosstab != totals.bar_graph)
+        .collect())
+}
@@ -0,0 +1,263 @@
+use std::fmt;
+
+use super::cross_check::GradeTotalMismatch;
+use super::schema::MissingName;
+use std::time::Duration;
+
+/// The maximum number of characters of a response body quoted in an error message
+const SNIPPET_LENGTH: usize = 200;
+
+/// An error raised while talking to the Tableau VizQL server.
+#[derive(Debug)]
+pub enum NetworkError {
+    /// The HTTP request itself failed (connection, TLS, body decoding, ...).
+    Http(reqwest::Error),
+    /// The server answered with a non-2xx status code.
+    Status {
+        url: String,
+        status: reqwest::StatusCode,
+        snippet: String,
+    },
+    /// The view page doesn't contain the `#tsConfigContainer` element holding the session config.
+    MissingConfigContainer { url: String },
+    /// A response doesn't have the expected JSON shape.
+    UnexpectedJson { path: String, snippet: String },
+    /// A filter domain doesn't have the expected values.
+    InvalidFilterDomain { field: String, reason: String },
+    /// The dashboard no longer has sheets, filters or parameters the downloader depends on.
+    SchemaDrift {
+        missing: Vec<MissingName>,
+        /// The directory the raw responses were written to, if writing them succeeded
+        diagnostics: Option<String>,
+    },
+    /// The export crosstab dialog doesn't offer the requested sheet.
+    UnknownSheet {
+        name: String,
+        available: Vec<String>,
+    },
+    /// The server didn't answer, or stopped sending the response body, within the read timeout.
+    Timeout { url: String, after: Duration },
+    /// The response body ended before the length announced by its `Content-Length` header.
+    IncompleteBody {
+        url: String,
+        expected: u64,
+        received: u64,
+    },
+    /// An exported CSV file doesn't have the expected columns.
+    InvalidExport { path: String, reason: String },
+    /// The grade totals of a crosstab export don't match the bar graph, so the export is
+    /// incomplete.
+    GradeTotalsMismatch {
+        path: String,
+        mismatches: Vec<GradeTotalMismatch>,
+    },
+    /// Reading or writing a file failed.
+    File {
+        path: String,
+        source: std::io::Error,
+    },
+    /// Recording or replaying traffic failed.
+    Recording(String),
+    /// The configuration is invalid, e.g. an unreadable certificate or a request rate that isn't
+    /// positive.
+    Config(String),
+}
+
+impl NetworkError {
+    /// Builds an `UnexpectedJson` error quoting the start of `body`.
+    pub fn unexpected_json(path: &str, body: &str) -> Self {
+        NetworkError::UnexpectedJson {
+            path: path.to_string(),
+            snippet: snippet(body),
+        }
+    }
+
+    /// Builds a `File` error for `path`.
+    pub fn file(path: &std::path::Path, source: std::io::Error) -> Self {
+        NetworkError::File {
+            path: path.display().to_string(),
+            source,
+        }
+    }
+
+    /// Whether retrying the request may succeed: connection failures, timeouts,
+    /// rate limiting, server errors and truncated downloads.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            NetworkError::Http(err) => !err.is_builder() && !err.is_redirect(),
+            NetworkError::Status { status, .. } => {
+                status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            NetworkError::Timeout { .. } | NetworkError::IncompleteBody { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether exporting a smaller part of the data may succeed: the server kept failing or
+    /// timing out on the export, or the export disagrees with the bar graph.
+    pub fn suggests_splitting(&self) -> bool {
+        self.is_transient() || matches!(self, NetworkError::GradeTotalsMismatch { .. })
+    }
+
+    /// Whether the VizQL session expired, which the server reports with `410 Gone`.
+    pub fn is_session_expired(&self) -> bool {
+        matches!(
+            self,
+            NetworkError::Status { status, .. } if *status == reqwest::StatusCode::GONE
+        )
+    }
+
+    /// The process exit code to report this error with, following `sysexits.h`.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            // EX_UNAVAILABLE
+            NetworkError::Http(_)
+            | NetworkError::Status { .. }
+            | NetworkError::Timeout { .. }
+            | NetworkError::IncompleteBody { .. } => 69,
+            // EX_PROTOCOL
+            NetworkError::MissingConfigContainer { .. }
+            | NetworkError::UnexpectedJson { .. }
+            | NetworkError::InvalidFilterDomain { .. }
+            | NetworkError::SchemaDrift { .. }
+            | NetworkError::UnknownSheet { .. } => 76,
+            // EX_DATAERR
+            NetworkError::InvalidExport { .. } | NetworkError::GradeTotalsMismatch { .. } => 65,
+            // EX_IOERR
+            NetworkError::File { .. } | NetworkError::Recording(_) => 74,
+            // EX_CONFIG
+            NetworkError::Config(_) => 78,
+        }
+    }
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::Http(err) => write!(f, "HTTP request failed: {}", err),
+            NetworkError::Status {
+                url,
+                status,
+                snippet,
+            } => write!(f, "{} returned {}: {}", url, status, snippet),
+            NetworkError::MissingConfigContainer { url } => write!(
+                f,
+                "No #tsConfigContainer found in {}, the dashboard page may have changed",
+                url
+            ),
+            NetworkError::UnexpectedJson { path, snippet } => write!(
+                f,
+                "Unexpected JSON response, missing {}: {}",
+                if path.is_empty() { "/" } else { path },
+                snippet
+            ),
+            NetworkError::InvalidFilterDomain { field, reason } => {
+                write!(f, "Unexpected {} filter domain: {}", field, reason)
+            }
+            NetworkError::SchemaDrift {
+                missing,
+                diagnostics,
+            } => {
+                write!(f, "The dashboard changed:")?;
+                for name in missing {
+                    write!(f, "\n  - {}", name)?;
+                }
+                match diagnostics {
+                    Some(directory) => {
+                        write!(f, "\nThe raw responses were written to {}", directory)
+                    }
+                    None => Ok(()),
+                }
+            }
+            NetworkError::UnknownSheet { name, available } => write!(
+                f,
+                "The export dialog has no sheet {:?} (available: {})",
+                name,
+                available.join(", ")
+            ),
+            NetworkError::InvalidExport { path, reason } => {
+                write!(f, "Unexpected exported file {}: {}", path, reason)
+            }
+            NetworkError::GradeTotalsMismatch { path, mismatches } => {
+                write!(f, "{} doesn't match the bar graph:", path)?;
+                for mismatch in mismatches {
+                    write!(f, "\n  - {}", mismatch)?;
+                }
+                Ok(())
+            }
+            NetworkError::Timeout { url, after } => write!(
+                f,
+                "{} timed out after {:.1}s without receiving data",
+                url,
+                after.as_secs_f32()
+            ),
+            NetworkError::IncompleteBody {
+                url,
+                expected,
+                received,
+            } => write!(f, "{} ended after {} of {} bytes", url, received, expected),
+            NetworkError::File { path, source } => {
+                write!(f, "I/O error on {}: {}", path, source)
+            }
+            NetworkError::Recording(message) | NetworkError::Config(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NetworkError::Http(err) => Some(err),
+            NetworkError::File { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for NetworkError {
+    fn from(err: reqwest::Error) -> Self {
+        NetworkError::Http(err)
+    }
+}
+
+/// Returns the start of a response body, for quoting in error messages.
+pub fn snippet(body: &str) -> String {
+    let body: &str = body.trim();
+    match body.char_indices().nth(SNIPPET_LENGTH) {
+        Some((end, _)) => format!("{}...", &body[..end]),
+        None => body.to_string(),
+    }
+}
+
+/// Looks up the string at a JSON pointer (e.g. `/vqlCmdResponse/cmdResultList/0`) in a response body.
+///
+/// # Returns
+///
+/// * `Ok(String)` - The string found at `pointer`.
+/// * `Err(NetworkError::UnexpectedJson)` - With the longest prefix of `pointer` that failed to resolve.
+pub fn json_str_at(body: &str, pointer: &str) -> Result<String, NetworkError> {
+    let json: serde_json::Value =
+        serde_json::from_str(body).map_err(|_| NetworkError::unexpected_json("", body))?;
+
+    let mut value: &serde_json::Value = &json;
+    let mut path: String = String::new();
+    for segment in pointer.split('/').skip(1) {
+        path.push('/');
+        path.push_str(segment);
+        let next: Option<&serde_json::Value> = match value {
+            serde_json::Value::Object(map) => map.get(segment),
+            serde_json::Value::Array(values) => {
+                segment.parse::<usize>().ok().and_then(|i| values.get(i))
+            }
+            _ => None,
+        };
+        value = next.ok_or_else(|| NetworkError::unexpected_json(&path, body))?;
+    }
+
+    value
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| NetworkError::unexpected_json(&path, body))
+}
@@ -0,0 +1,713 @@
+//! An in-process mock of the Tableau VizQL server, for testing the downloader offline.
+//!
+//! The mock serves the crosstab view page and the VizQL commands used by `TableauSession`
+//! over plain HTTP/1.1 on localhost. It keeps per-session filter state like the real server,
+//! so the exported CSV depends on the selected academic year.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// The academic years of the mock `ACADEMIC_YEAR_SPAN` filter domain
+pub const ACADEMIC_YEARS: [&str; 3] = ["2020-2021", "2021-2022", "2022-2023"];
+
+/// The course prefixes of the mock `COURSE_PREFIX` filter domain
+pub const COURSE_PREFIXES: [&str; 2] = ["C S", "M"];
+
+/// The semesters of the mock `Calculation_3161245480939225089` filter domain
+pub const SEMESTERS: [&str; 3] = ["Fall", "Spring", "Summer"];
+
+/// The datasource of the mock dashboard, unlike the real one so that it has to be discovered
+const DATASOURCE: &str = "sqlproxy.0mockdatasource0";
+
+/// The calculated field filtering semesters
+const SEMESTER_FIELD: &str = "Calculation_3161245480939225089";
+
+/// The value of `[Parameters].[Parameter 1]` grouping grades by letter
+pub const CONDENSED: &str = "Condensed";
+
+/// How long stalled requests wait before being answered
+const STALL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// A request received by the mock server.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub path: String,
+    /// The headers, by lowercase name
+    pub headers: HashMap<String, String>,
+    pub fields: HashMap<String, String>,
+}
+
+/// The state of a VizQL session on the mock server.
+#[derive(Debug, Default)]
+struct Session {
+    bootstrapped: bool,
+    /// The `ACADEMIC_YEAR_SPAN` filter domain of the session
+    academic_years: Vec<String>,
+    year_index: Option<usize>,
+    /// The selected `COURSE_PREFIX` indices, or `None` for all
+    department_indices: Option<Vec<usize>>,
+    /// The selected semesters, or `None` for all
+    semesters: Option<Vec<String>>,
+    /// The value of `[Parameters].[Parameter 1]`, or `None` for the default
+    grade_view: Option<String>,
+    /// The exports by result key, with the filters they were exported with
+    exports: HashMap<String, Export>,
+}
+
+/// The filters an export was made with.
+#[derive(Debug, Clone)]
+struct Export {
+    academic_year: String,
+    department_indices: Option<Vec<usize>>,
+    semesters: Option<Vec<String>>,
+    grade_view: Option<String>,
+    /// Whether the bar graph sheet was exported, rather than the crosstab
+    bar_graph: bool,
+    /// Whether the export misses its last row
+    short: bool,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    sessions: HashMap<String, Session>,
+    next_session: usize,
+    requests: Vec<Request>,
+    /// The number of upcoming requests to answer with `503 Service Unavailable`
+    failures: usize,
+    /// The number of upcoming requests to answer only after `STALL`
+    stalls: usize,
+    /// The number of upcoming exports to cut off halfway through their body
+    truncations: usize,
+    /// The number of upcoming crosstab exports to drop the last row of
+    short_exports: usize,
+    /// The most rows a crosstab export may have before the server fails it, or `None` for no limit
+    export_row_limit: Option<usize>,
+    /// Replacements applied to the bootstrap response, to simulate a changed dashboard
+    renames: Vec<(String, String)>,
+    /// The number of exports to serve before every session expires, or `None` to keep them
+    downloads_before_expiry: Option<usize>,
+    /// An academic year listed first by the sessions opened after the given number of sessions
+    published_academic_year: Option<(usize, String)>,
+}
+
+/// A running mock Tableau server.
+#[derive(Clone)]
+pub struct MockTableauServer {
+    pub base_url: String,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockTableauServer {
+    /// Starts a mock server on a free localhost port.
+    pub async fn start() -> Self {
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server: MockTableauServer = MockTableauServer {
+            base_url: format!("http://{}", listener.local_addr().unwrap()),
+            state: Arc::new(Mutex::new(State::default())),
+        };
+
+        let handler: MockTableauServer = server.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler: MockTableauServer = handler.clone();
+                tokio::spawn(async move { handler.serve(stream).await });
+            }
+        });
+
+        server
+    }
+
+    /// The requests received so far.
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Answers the next `count` requests with `503 Service Unavailable`.
+    pub fn fail_next_requests(&self, count: usize) {
+        self.state.lock().unwrap().failures = count;
+    }
+
+    /// Answers the next `count` requests only after a long delay, to trigger read timeouts.
+    pub fn stall_next_requests(&self, count: usize) {
+        self.state.lock().unwrap().stalls = count;
+    }
+
+    /// Cuts off the body of the next `count` downloaded exports halfway, after announcing
+    /// their full length.
+    pub fn truncate_next_downloads(&self, count: usize) {
+        self.state.lock().unwrap().truncations = count;
+    }
+
+    /// Drops the last row of the next `count` crosstab exports, while the bar graph keeps
+    /// counting it.
+    pub fn shorten_next_exports(&self, count: usize) {
+        self.state.lock().unwrap().short_exports = count;
+    }
+
+    /// Answers exports of more than `rows` crosstab rows with `500 Internal Server Error`, as if
+    /// they were too large for the server.
+    pub fn limit_export_rows(&self, rows: usize) {
+        self.state.lock().unwrap().export_row_limit = Some(rows);
+    }
+
+    /// Renames `from` to `to` in bootstrap responses, as if the dashboard had been edited.
+    pub fn rename_in_bootstrap(&self, from: &str, to: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .renames
+            .push((from.to_string(), to.to_string()));
+    }
+
+    /// Forgets every session, so that further commands on them answer `410 Gone`.
+    pub fn expire_sessions(&self) {
+        self.state.lock().unwrap().sessions.clear();
+    }
+
+    /// Forgets every session once `count` more exports have been downloaded, as if they expired
+    /// in the middle of a download.
+    pub fn expire_sessions_after_downloads(&self, count: usize) {
+        self.state.lock().unwrap().downloads_before_expiry = Some(count);
+    }
+
+    /// Lists `academic_year` first in the academic years of the sessions opened after the first
+    /// `sessions`, as if it had been published in the middle of a download.
+    pub fn publish_academic_year(&self, sessions: usize, academic_year: &str) {
+        self.state.lock().unwrap().published_academic_year =
+            Some((sessions, academic_year.to_string()));
+    }
+
+    /// The CSV exported by the mock crosstab for an academic year and department prefixes.
+    pub fn crosstab_csv(academic_year: &str, course_prefixes: &[&str]) -> String {
+        Self::filtered_crosstab_csv(academic_year, course_prefixes, &SEMESTERS)
+    }
+
+    /// The CSV exported by the mock crosstab for an academic year, department prefixes and
+    /// semesters.
+    pub fn filtered_crosstab_csv(
+        academic_year: &str,
+        course_prefixes: &[&str],
+        semesters: &[&str],
+    ) -> String {
+        let start_year: u16 = academic_year[..4].parse().unwrap();
+        let fall: String = format!("Fall {}", start_year);
+        let spring: String = format!("Spring {}", start_year + 1);
+        let rows: [(&str, &str, String); 3] = [
+            ("C S", "Fall", format!("{fall},12345,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,A,\"1,024\"\n")),
+            ("C S", "Fall", format!("{fall},12345,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,B,12\n")),
+            ("M", "Spring", format!("{spring},54321,Mathematics,M,408C,DIFFERENTIAL AND INTEGRAL CALCULUS,M 408C DIFFERENTIAL AND INTEGRAL CALCULUS,A-,7\n")),
+        ];
+
+        let mut csv: String = "Semester,Section,Department,Department Code,Course Number,\
+                               Course Title,Course Full Title,Letter Grade,Count of letter grade\n"
+            .to_string();
+        for (course_prefix, semester, row) in rows {
+            if course_prefixes.contains(&course_prefix) && semesters.contains(&semester) {
+                csv.push_str(&row);
+            }
+        }
+
+        csv
+    }
+
+    /// The CSV exported by the mock crosstab in the condensed grade view, where grades are
+    /// grouped by letter, e.g. `A-` counts as `A`.
+    pub fn condensed_csv(crosstab_csv: &str) -> String {
+        let mut reader = csv::Reader::from_reader(crosstab_csv.as_bytes());
+        let mut writer = csv::WriterBuilder::new()
+            .terminator(csv::Terminator::Any(b'\n'))
+            .from_writer(Vec::new());
+        writer.write_record(reader.headers().unwrap()).unwrap();
+        let mut rows: Vec<(Vec<String>, u64)> = Vec::new();
+        for record in reader.records() {
+            let record: csv::StringRecord = record.unwrap();
+            let mut key: Vec<String> = record.iter().take(8).map(str::to_string).collect();
+            key[7] = key[7].trim_end_matches(['+', '-']).to_string();
+            let count: u64 = record[8].replace(',', "").parse().unwrap();
+            match rows.iter_mut().find(|(row, _)| row == &key) {
+                Some((_, total)) => *total += count,
+                None => rows.push((key, count)),
+            }
+        }
+        for (mut row, count) in rows {
+            row.push(count.to_string());
+            writer.write_record(&row).unwrap();
+        }
+
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    /// The CSV exported by the mock bar graph: the total count of each grade of the crosstab.
+    pub fn bar_graph_csv(crosstab_csv: &str) -> String {
+        let mut totals: Vec<(String, u64)> = Vec::new();
+        for record in csv::Reader::from_reader(crosstab_csv.as_bytes()).records() {
+            let record: csv::StringRecord = record.unwrap();
+            let count: u64 = record[8].replace(',', "").parse().unwrap();
+            match totals.iter_mut().find(|(grade, _)| grade == &record[7]) {
+                Some((_, total)) => *total += count,
+                None => totals.push((record[7].to_string(), count)),
+            }
+        }
+
+        let mut csv: String = "Letter Grade,Count of letter grade\n".to_string();
+        for (grade, total) in totals {
+            csv.push_str(&format!("{},{}\n", grade, total));
+        }
+
+        csv
+    }
+
+    async fn serve(&self, mut stream: TcpStream) {
+        let Some(request) = read_request(&mut stream).await else {
+            return;
+        };
+        let (status, content_type, body) = self.handle(&request);
+        let stalled: bool = {
+            let mut state = self.state.lock().unwrap();
+            let stalled: bool = state.stalls > 0;
+            state.stalls = state.stalls.saturating_sub(1);
+            stalled
+        };
+        if stalled {
+            tokio::time::sleep(STALL).await;
+        }
+        let truncated: bool = request.path.contains("/tempfile/") && status == "200 OK" && {
+            let mut state = self.state.lock().unwrap();
+            let truncated: bool = state.truncations > 0;
+            state.truncations = state.truncations.saturating_sub(1);
+            truncated
+        };
+        if request.path.contains("/tempfile/") && status == "200 OK" {
+            let mut state = self.state.lock().unwrap();
+            match state.downloads_before_expiry {
+                Some(count) if count > 1 => state.downloads_before_expiry = Some(count - 1),
+                Some(_) => {
+                    state.downloads_before_expiry = None;
+                    state.sessions.clear();
+                }
+                None => {}
+            }
+        }
+
+        // Like the real server, the view page sets cookies that later requests send back
+        let cookie: &str = if request.path.starts_with("/views/") && status == "200 OK" {
+            "Set-Cookie: tableau_locale=en; Path=/\r\n"
+        } else {
+            ""
+        };
+        let response: String = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+            status,
+            content_type,
+            body.len(),
+            cookie
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+        let body: &[u8] = if truncated {
+            &body.as_bytes()[..body.len() / 2]
+        } else {
+            body.as_bytes()
+        };
+        let _ = stream.write_all(body).await;
+        let _ = stream.shutdown().await;
+    }
+
+    fn handle(&self, request: &Request) -> (&'static str, &'static str, String) {
+        let mut state = self.state.lock().unwrap();
+        state.requests.push(request.clone());
+
+        if state.failures > 0 {
+            state.failures -= 1;
+            return (
+                "503 Service Unavailable",
+                "text/plain",
+                "Try again".to_string(),
+            );
+        }
+
+        let path: &str = request.path.split('?').next().unwrap_or_default();
+        if path.starts_with("/views/") {
+            let session_id: String = format!("MOCK-SESSION-{}", state.next_session);
+            let mut academic_years: Vec<String> =
+                ACADEMIC_YEARS.iter().map(|year| year.to_string()).collect();
+            if let Some((sessions, academic_year)) = &state.published_academic_year {
+                if state.next_session >= *sessions {
+                    academic_years.insert(0, academic_year.clone());
+                }
+            }
+            state.next_session += 1;
+            state.sessions.insert(
+                session_id.clone(),
+                Session {
+                    academic_years,
+                    ..Session::default()
+                },
+            );
+            return ("200 OK", "text/html", view_page(&session_id));
+        }
+
+        let Some((_, vizql_path)) = path.split_once("/vizql/w/") else {
+            return not_found();
+        };
+        // Skip `{workbook}/v/{view}/`
+        let endpoint: String = vizql_path
+            .splitn(4, '/')
+            .nth(3)
+            .unwrap_or_default()
+            .to_string();
+
+        let (session_id, command): (&str, &str) =
+            if let Some(rest) = endpoint.strip_prefix("bootstrapSession/sessions/") {
+                (rest, "bootstrapSession")
+            } else if let Some(rest) = endpoint.strip_prefix("tempfile/sessions/") {
+                (rest.trim_end_matches('/'), "tempfile")
+            } else if let Some(rest) = endpoint.strip_prefix("sessions/") {
+                match rest.split_once("/commands/") {
+                    Some(parts) => parts,
+                    None => return not_found(),
+                }
+            } else {
+                return not_found();
+            };
+
+        let renames: Vec<(String, String)> = state.renames.clone();
+        let Some(session) = state.sessions.get_mut(session_id) else {
+            return ("410 Gone", "text/plain", "Session expired".to_string());
+        };
+
+        match command {
+            "bootstrapSession" => {
+                session.bootstrapped = true;
+                (
+                    "200 OK",
+                    "text/plain",
+                    bootstrap_response(&session.academic_years, &renames),
+                )
+            }
+            _ if !session.bootstrapped => (
+                "400 Bad Request",
+                "text/plain",
+                "Session not bootstrapped".to_string(),
+            ),
+            "tabdoc/categorical-filter" => {
+                let field: &str = request
+                    .fields
+                    .get("globalFieldName")
+                    .map_or("", String::as_str);
+                let values: Option<Vec<String>> =
+                    match request.fields.get("filterUpdateType").map(String::as_str) {
+                        Some("filter-replace") => request
+                            .fields
+                            .get("filterValues")
+                            .and_then(|values| serde_json::from_str(values).ok()),
+                        _ => None,
+                    };
+                if field == global_field_name("COURSE_PREFIX") {
+                    session.department_indices = values.map(|values| {
+                        values
+                            .iter()
+                            .filter_map(|value| COURSE_PREFIXES.iter().position(|p| p == value))
+                            .collect()
+                    });
+                } else if field == global_field_name(SEMESTER_FIELD) {
+                    session.semesters = values;
+                }
+                ("200 OK", "application/json", command_response())
+            }
+            "tabdoc/set-parameter-value" => {
+                if request.fields.get("globalFieldName").map(String::as_str)
+                    == Some("[Parameters].[Parameter 1]")
+                {
+                    session.grade_view = request.fields.get("valueString").cloned();
+                }
+                ("200 OK", "application/json", command_response())
+            }
+            "tabdoc/categorical-filter-by-index" => {
+                let indices: Vec<usize> = request
+                    .fields
+                    .get("filterIndices")
+                    .and_then(|indices| serde_json::from_str(indices).ok())
+                    .unwrap_or_default();
+                let field: &str = request
+                    .fields
+                    .get("globalFieldName")
+                    .map_or("", String::as_str);
+                if field == global_field_name("ACADEMIC_YEAR_SPAN") {
+                    session.year_index = indices.first().copied();
+                } else if field == global_field_name("COURSE_PREFIX") {
+                    session.department_indices = Some(indices);
+                }
+                ("200 OK", "application/json", command_response())
+            }
+            "tabsrv/export-crosstab-server-dialog" => (
+                "200 OK",
+                "application/json",
+                serde_json::json!({
+                    "vqlCmdResponse": {"layoutStatus": {"applicationPresModel": {
+                        "presentationLayerNotification": [{"presModelHolder": {
+                            "genExportCrosstabOptionsDialogPresModel": {
+                                "thumbnailSheetPickerItems": [
+                                    {"sheetName": "External dashboard-Crosstab", "sheetdocId": "crosstab-doc"},
+                                    {"sheetName": "External dashboard-bar graph", "sheetdocId": "bar-graph-doc"}
+                                ]
+                            }
+                        }}]
+                    }}}
+                })
+                .to_string(),
+            ),
+            "tabsrv/export-crosstab-to-csvserver" => {
+                let Some(year_index) = session.year_index else {
+                    return ("400 Bad Request", "text/plain", "No year selected".to_string());
+                };
+                let bar_graph: bool = request
+                    .fields
+                    .get("sheetdocId")
+                    .is_some_and(|id| id == "bar-graph-doc");
+                let academic_year: String = session.academic_years[year_index].clone();
+                let rows: usize = Self::filtered_crosstab_csv(
+                    &academic_year,
+                    &session_course_prefixes(session),
+                    &session_semesters(session),
+                )
+                .lines()
+                .count()
+                    - 1;
+                if state.export_row_limit.is_some_and(|limit| rows > limit) {
+                    return (
+                        "500 Internal Server Error",
+                        "text/plain",
+                        "Export too large".to_string(),
+                    );
+                }
+                let short: bool = !bar_graph && state.short_exports > 0;
+                if short {
+                    state.short_exports -= 1;
+                }
+                let Some(session) = state.sessions.get_mut(session_id) else {
+                    return ("410 Gone", "text/plain", "Session expired".to_string());
+                };
+                let key: String = format!("export-{}", session.exports.len());
+                let export: Export = Export {
+                    academic_year,
+                    department_indices: session.department_indices.clone(),
+                    semesters: session.semesters.clone(),
+                    grade_view: session.grade_view.clone(),
+                    bar_graph,
+                    short,
+                };
+                session.exports.insert(key.clone(), export);
+                (
+                    "200 OK",
+                    "application/json",
+                    serde_json::json!({
+                        "vqlCmdResponse": {"cmdResultList": [
+                            {"commandReturn": {"exportResult": {"resultKey": key}}}
+                        ]}
+                    })
+                    .to_string(),
+                )
+            }
+            "tempfile" => {
+                let key: &str = request
+                    .path
+                    .split_once("?key=")
+                    .map(|(_, key)| key)
+                    .unwrap_or_default();
+                match session.exports.get(key) {
+                    Some(export) => {
+                        let course_prefixes: Vec<&str> = match &export.department_indices {
+                            Some(indices) => indices.iter().map(|&i| COURSE_PREFIXES[i]).collect(),
+                            None => COURSE_PREFIXES.to_vec(),
+                        };
+                        let semesters: Vec<&str> = match &export.semesters {
+                            Some(semesters) => semesters.iter().map(String::as_str).collect(),
+                            None => SEMESTERS.to_vec(),
+                        };
+                        let mut crosstab: String = Self::filtered_crosstab_csv(
+                            &export.academic_year,
+                            &course_prefixes,
+                            &semesters,
+                        );
+                        if export.grade_view.as_deref() == Some(CONDENSED) {
+                            crosstab = Self::condensed_csv(&crosstab);
+                        }
+                        let csv: String = if export.bar_graph {
+                            Self::bar_graph_csv(&crosstab)
+                        } else if export.short {
+                            let mut lines: Vec<&str> = crosstab.lines().collect();
+                            lines.pop();
+                            lines.iter().map(|line| format!("{}\n", line)).collect()
+                        } else {
+                            crosstab
+                        };
+                        ("200 OK", "text/csv", csv)
+                    }
+                    None => not_found(),
+                }
+            }
+            _ => not_found(),
+        }
+    }
+}
+
+/// The department prefixes selected in a session.
+fn session_course_prefixes(session: &Session) -> Vec<&'static str> {
+    match &session.department_indices {
+        Some(indices) => indices.iter().map(|&i| COURSE_PREFIXES[i]).collect(),
+        None => COURSE_PREFIXES.to_vec(),
+    }
+}
+
+/// The semesters selected in a session.
+fn session_semesters(session: &Session) -> Vec<&str> {
+    match &session.semesters {
+        Some(semesters) => semesters.iter().map(String::as_str).collect(),
+        None => SEMESTERS.to_vec(),
+    }
+}
+
+fn not_found() -> (&'static str, &'static str, String) {
+    ("404 Not Found", "text/plain", "Not found".to_string())
+}
+
+/// The crosstab view page, holding the session config in `#tsConfigContainer`.
+fn view_page(session_id: &str) -> String {
+    format!(
+        "<html><body><textarea id=\"tsConfigContainer\">{}</textarea></body></html>",
+        serde_json::json!({"sessionid": session_id})
+    )
+}
+
+fn global_field_name(field_name: &str) -> String {
+    format!("[{}].[none:{}:nk]", DATASOURCE, field_name)
+}
+
+/// Builds a categorical quick filter zone of the dashboard presentation model.
+fn filter_zone(field_name: &str, labels: &[impl AsRef<str>]) -> serde_json::Value {
+    serde_json::json!({
+        "presModelHolder": {"quickFilterDisplay": {"quickFilter": {"categoricalFilter": {
+            "fn": global_field_name(field_name),
+            "domainTables": [{
+                "domain": labels.iter().map(|label| serde_json::json!({"label": label.as_ref()})).collect::<Vec<_>>()
+            }]
+        }}}}
+    })
+}
+
+/// The length-prefixed `bootstrapSession` response listing `academic_years`, with `renames`
+/// applied to its documents.
+fn bootstrap_response(academic_years: &[String], renames: &[(String, String)]) -> String {
+    let rename = |document: String| -> String {
+        renames
+            .iter()
+            .fold(document, |document, (from, to)| document.replace(from, to))
+    };
+    let primary: String =
+        rename(serde_json::json!({"sheetName": "External dashboard-Crosstab"}).to_string());
+    let secondary: String = serde_json::json!({
+        "secondaryInfo": {"presModelMap": {"workbookPresModel": {"dashboardPresModel": {"zones": {
+            "1": filter_zone("ACADEMIC_YEAR_SPAN", academic_years),
+            "2": filter_zone("COURSE_PREFIX", &COURSE_PREFIXES),
+            "3": filter_zone(SEMESTER_FIELD, &SEMESTERS),
+            "4": {"presModelHolder": {"visual": {"visualIdPresModel": {
+                "worksheet": "Grade distribution - external",
+                "dashboard": "External dashboard-Crosstab"
+            }}}},
+            "5": {"presModelHolder": {"parameterControl": {
+                "fieldCaption": "Parameter 1",
+                "parameterName": "[Parameters].[Parameter 1]"
+            }}},
+        }}}}}
+    })
+    .to_string();
+    let secondary: String = rename(secondary);
+
+    format!(
+        "{};{}{};{}",
+        primary.len(),
+        primary,
+        secondary.len(),
+        secondary
+    )
+}
+
+fn command_response() -> String {
+    serde_json::json!({"vqlCmdResponse": {"cmdResultList": []}}).to_string()
+}
+
+/// Reads an HTTP/1.1 request, decoding `multipart/form-data` and urlencoded bodies into fields.
+async fn read_request(stream: &mut TcpStream) -> Option<Request> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let header_end: usize = loop {
+        let mut chunk: [u8; 4096] = [0; 4096];
+        let read: usize = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+    };
+
+    let head: String = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let _method: &str = request_line.next()?;
+    let path: String = request_line.next()?.to_string();
+
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let content_length: usize = headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    while buffer.len() < header_end + content_length {
+        let mut chunk: [u8; 4096] = [0; 4096];
+        let read: usize = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+    let body: String = String::from_utf8_lossy(&buffer[header_end..]).to_string();
+
+    let content_type: &str = headers.get("content-type").map_or("", String::as_str);
+    let fields: HashMap<String, String> = match content_type.split_once("boundary=") {
+        Some((_, boundary)) => parse_multipart(&body, boundary),
+        None if content_type.starts_with("application/x-www-form-urlencoded") => body
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    value.replace('+', " ").replace("%20", " "),
+                )
+            })
+            .collect(),
+        None => HashMap::new(),
+    };
+
+    Some(Request {
+        path,
+        headers,
+        fields,
+    })
+}
+
+fn parse_multipart(body: &str, boundary: &str) -> HashMap<String, String> {
+    body.split(&format!("--{}", boundary))
+        .filter_map(|part| {
+            let (part_headers, value) = part.split_once("\r\n\r\n")?;
+            let name: &str = part_headers.split("name=\"").nth(1)?.split('"').next()?;
+            Some((name.to_string(), value.trim_end_matches("\r\n").to_string()))
+        })
+        .collect()
+}
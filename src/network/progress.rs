@@ -0,0 +1,91 @@
+//! The progress bars of downloads: those of `indicatif` with the `progress` feature, or bars that
+//! draw nothing without it, so that downloads report the same steps either way.
+
+#[cfg(feature = "progress")]
+pub use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+#[cfg(not(feature = "progress"))]
+pub use silent::{MultiProgress, ProgressBar, ProgressStyle};
+
+#[cfg(not(feature = "progress"))]
+mod silent {
+    use std::borrow::Cow;
+    use std::convert::Infallible;
+    use std::time::Duration;
+
+    /// A progress bar that draws nothing, but still prints the lines given to `println`.
+    #[derive(Debug, Clone, Default)]
+    pub struct ProgressBar;
+
+    impl ProgressBar {
+        pub fn new(_length: u64) -> Self {
+            ProgressBar
+        }
+
+        pub fn new_spinner() -> Self {
+            ProgressBar
+        }
+
+        pub fn hidden() -> Self {
+            ProgressBar
+        }
+
+        pub fn set_message(&self, _message: impl Into<Cow<'static, str>>) {}
+
+        pub fn set_prefix(&self, _prefix: impl Into<Cow<'static, str>>) {}
+
+        pub fn set_style(&self, _style: ProgressStyle) {}
+
+        pub fn set_length(&self, _length: u64) {}
+
+        pub fn set_position(&self, _position: u64) {}
+
+        pub fn inc(&self, _delta: u64) {}
+
+        pub fn enable_steady_tick(&self, _interval: Duration) {}
+
+        pub fn println(&self, line: impl AsRef<str>) {
+            eprintln!("{}", line.as_ref());
+        }
+
+        pub fn suspend<F: FnOnce() -> R, R>(&self, f: F) -> R {
+            f()
+        }
+
+        pub fn finish_and_clear(&self) {}
+
+        pub fn finish_with_message(&self, _message: impl Into<Cow<'static, str>>) {}
+    }
+
+    /// The style of a progress bar, which is never drawn.
+    #[derive(Debug, Clone)]
+    pub struct ProgressStyle;
+
+    impl ProgressStyle {
+        pub fn with_template(_template: &str) -> Result<Self, Infallible> {
+            Ok(ProgressStyle)
+        }
+
+        pub fn progress_chars(self, _chars: &str) -> Self {
+            self
+        }
+    }
+
+    /// A set of progress bars, which are never drawn.
+    #[derive(Debug, Default)]
+    pub struct MultiProgress;
+
+    impl MultiProgress {
+        pub fn new() -> Self {
+            MultiProgress
+        }
+
+        pub fn add(&self, bar: ProgressBar) -> ProgressBar {
+            bar
+        }
+
+        pub fn insert(&self, _index: usize, bar: ProgressBar) -> ProgressBar {
+            bar
+        }
+    }
+}
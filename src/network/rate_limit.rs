@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use super::error::NetworkError;
+
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Spaces out requests shared by every session of a run, to stay polite to the server.
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    /// Allows at most `requests_per_second` requests per second.
+    ///
+    /// # Returns
+    /// A `NetworkError::Config` if the rate isn't a positive finite number, or is so small that
+    /// the interval between requests can't be represented.
+    pub fn new(requests_per_second: f64) -> Result<Self, NetworkError> {
+        if !requests_per_second.is_finite() || requests_per_second <= 0.0 {
+            return Err(NetworkError::Config(format!(
+                "The request rate must be a positive number, not {}",
+                requests_per_second
+            )));
+        }
+        let interval: Duration =
+            Duration::try_from_secs_f64(1.0 / requests_per_second).map_err(|_| {
+                NetworkError::Config(format!(
+                    "The request rate {} is too small",
+                    requests_per_second
+                ))
+            })?;
+
+        Ok(RateLimiter {
+            interval,
+            next: Mutex::new(None),
+        })
+    }
+
+    /// Waits until the next request may be sent.
+    pub async fn wait(&self) {
+        let mut next = self.next.lock().await;
+        if let Some(instant) = *next {
+            tokio::time::sleep_until(instant).await;
+        }
+        *next = Some(Instant::now() + self.interval);
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// The maximum number of close matches suggested for a missing name
+const MAX_SUGGESTIONS: usize = 3;
+
+/// What a name expected on the dashboard designates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameKind {
+    Sheet,
+    Filter,
+    Parameter,
+}
+
+impl fmt::Display for NameKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NameKind::Sheet => write!(f, "sheet"),
+            NameKind::Filter => write!(f, "filter"),
+            NameKind::Parameter => write!(f, "parameter"),
+        }
+    }
+}
+
+/// The names the downloader depends on, which must all appear in the bootstrap response.
+#[derive(Debug, Clone, Copy)]
+pub struct ExpectedNames<'a> {
+    /// Worksheet and dashboard names, e.g. `Grade distribution - external`
+    pub sheets: &'a [&'a str],
+    /// Columns of categorical filters, e.g. `COURSE_PREFIX`
+    pub filters: &'a [&'a str],
+    /// Global names of parameters, e.g. `[Parameters].[Parameter 1]`
+    pub parameters: &'a [&'a str],
+}
+
+/// A name the downloader depends on that the dashboard no longer has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingName {
+    pub kind: NameKind,
+    pub expected: String,
+    /// Names of the dashboard close to the expected one, closest first
+    pub suggestions: Vec<String>,
+}
+
+impl fmt::Display for MissingName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:?} not found", self.kind, self.expected)?;
+        if !self.suggestions.is_empty() {
+            let suggestions: Vec<String> = self
+                .suggestions
+                .iter()
+                .map(|suggestion| format!("{:?}", suggestion))
+                .collect();
+            write!(f, " (did you mean {}?)", suggestions.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that the names the downloader depends on appear in the bootstrap response.
+///
+/// # Arguments
+///
+/// * `documents` - The JSON documents of the bootstrap response.
+/// * `filter_fields` - The columns of the categorical filters found in the response.
+/// * `expected` - The names to look for.
+///
+/// # Returns
+///
+/// The missing names with close matches among the names of the same kind in the response,
+/// or an empty list if every name was found.
+pub fn check_schema(
+    documents: &[serde_json::Value],
+    filter_fields: &[String],
+    expected: &ExpectedNames,
+) -> Vec<MissingName> {
+    let mut strings: BTreeSet<String> = BTreeSet::new();
+    for document in documents {
+        collect_strings(document, &mut strings);
+    }
+    let parameters: Vec<String> = strings
+        .iter()
+        .filter(|string| string.starts_with("[Parameters]."))
+        .cloned()
+        .collect();
+    let sheets: Vec<String> = strings
+        .iter()
+        .filter(|string| string.chars().count() <= 100 && !string.starts_with('['))
+        .cloned()
+        .collect();
+
+    let mut missing: Vec<MissingName> = Vec::new();
+    for (kind, names, candidates) in [
+        (NameKind::Sheet, expected.sheets, &sheets),
+        (NameKind::Filter, expected.filters, &filter_fields.to_vec()),
+        (NameKind::Parameter, expected.parameters, &parameters),
+    ] {
+        for name in names {
+            if !candidates.iter().any(|candidate| candidate == name) {
+                missing.push(MissingName {
+                    kind,
+                    expected: name.to_string(),
+                    suggestions: close_matches(name, candidates),
+                });
+            }
+        }
+    }
+
+    missing
+}
+
+/// Collects every string value of a JSON document.
+fn collect_strings(value: &serde_json::Value, strings: &mut BTreeSet<String>) {
+    match value {
+        serde_json::Value::String(string) => {
+            strings.insert(string.clone());
+        }
+        serde_json::Value::Array(values) => {
+            for child in values {
+                collect_strings(child, strings);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for child in map.values() {
+                collect_strings(child, strings);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Finds the candidates close to `name`, closest first.
+///
+/// A candidate is close if it equals `name` ignoring case, or if its edit distance to `name` is
+/// at most a third of the length of `name`.
+fn close_matches(name: &str, candidates: &[String]) -> Vec<String> {
+    let name_lowercase: String = name.to_lowercase();
+    let max_distance: usize = (name.chars().count() / 3).max(1);
+
+    let mut matches: Vec<(usize, &String)> = candidates
+        .iter()
+        .map(|candidate| {
+            (
+                edit_distance(&name_lowercase, &candidate.to_lowercase()),
+                candidate,
+            )
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    matches.sort();
+
+    matches
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.clone())
+        .collect()
+}
+
+/// The Levenshtein distance between two strings, in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current: Vec<usize> = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution: usize = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
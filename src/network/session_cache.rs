@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::error::NetworkError;
+use super::DashboardFilter;
+
+/// Cached sessions older than this are discarded without trying them
+pub const MAX_SESSION_AGE: Duration = Duration::from_secs(4 * 3600);
+
+/// A prepared VizQL session saved to disk, so that later runs can skip opening and
+/// bootstrapping a session while the server keeps it alive.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CachedSession {
+    /// The URL of the view the session was opened on
+    pub view_url: String,
+    pub session_id: String,
+    /// The raw session config of the view page
+    pub ts_config: String,
+    /// The `Cookie` header of the session, if the server set any cookie
+    pub cookies: Option<String>,
+    /// When the session was opened, in seconds since the Unix epoch
+    pub created_at: u64,
+    /// The categorical filters of the dashboard when the session was prepared
+    pub filters: Vec<DashboardFilter>,
+    /// The global field names of the filters no longer selecting all their values
+    pub narrowed_filters: BTreeSet<String>,
+}
+
+impl CachedSession {
+    /// Loads a cached session.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(CachedSession))` - If the file holds a session.
+    /// * `Ok(None)` - If there is no file, or it can't be parsed, e.g. after an upgrade.
+    /// * `Err(NetworkError::File)` - If the file can't be read.
+    pub fn load(path: &Path) -> Result<Option<Self>, NetworkError> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json).ok()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(NetworkError::file(path, err)),
+        }
+    }
+
+    /// Writes the cached session, replacing the previous one atomically.
+    pub fn save(&self, path: &Path) -> Result<(), NetworkError> {
+        if let Some(directory) = path.parent().filter(|directory| !directory.exists()) {
+            std::fs::create_dir_all(directory).map_err(|err| NetworkError::file(directory, err))?;
+        }
+        let mut tmp_path: std::ffi::OsString = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path: PathBuf = PathBuf::from(tmp_path);
+
+        let json: String = serde_json::to_string_pretty(self)
+            .map_err(|err| NetworkError::file(path, err.into()))?;
+        std::fs::write(&tmp_path, json).map_err(|err| NetworkError::file(&tmp_path, err))?;
+        std::fs::rename(&tmp_path, path).map_err(|err| NetworkError::file(path, err))
+    }
+
+    /// How long ago the session was opened.
+    pub fn age(&self) -> Duration {
+        Duration::from_secs(now().saturating_sub(self.created_at))
+    }
+}
+
+/// The current time, as stored in `CachedSession::created_at`.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
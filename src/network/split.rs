@@ -0,0 +1,91 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::error::NetworkError;
+
+/// The file a part of a split export is written to, e.g. `grade_distributions_2022-2023.csv.split0`.
+pub fn part_path(destination: &Path, part: usize) -> PathBuf {
+    let mut path: std::ffi::OsString = destination.as_os_str().to_owned();
+    path.push(format!(".split{}", part));
+
+    PathBuf::from(path)
+}
+
+/// Removes the parts of a split export that failed, so that they don't linger next to the output.
+///
+/// Parts that were never written are skipped, and failures are ignored since the export already
+/// failed.
+pub fn remove_parts(parts: &[PathBuf]) {
+    for part in parts {
+        let _ = std::fs::remove_file(part);
+    }
+}
+
+/// Concatenates the CSV exports of the parts of a split export into `destination` and removes
+/// the parts.
+///
+/// The header of the first part is kept and the header of every other part is skipped, so the
+/// result reads like a single export. Parts without any row may be empty.
+///
+/// # Returns
+///
+/// * `Ok(u64)` - The size of `destination`.
+/// * `Err(NetworkError::InvalidExport)` - If the parts have different headers.
+/// * `Err(NetworkError::File)` - If a part can't be read or `destination` can't be written.
+pub fn concatenate_parts(parts: &[PathBuf], destination: &Path) -> Result<u64, NetworkError> {
+    let mut tmp_path: std::ffi::OsString = destination.as_os_str().to_owned();
+    tmp_path.push(".part");
+    let tmp_path: PathBuf = PathBuf::from(tmp_path);
+
+    let mut file: std::fs::File =
+        std::fs::File::create(&tmp_path).map_err(|err| NetworkError::file(&tmp_path, err))?;
+    let mut header: Option<Vec<u8>> = None;
+    let mut bytes: u64 = 0;
+    for part in parts {
+        let contents: Vec<u8> = std::fs::read(part).map_err(|err| NetworkError::file(part, err))?;
+        if contents.is_empty() {
+            continue;
+        }
+        let header_end: usize = contents
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(contents.len(), |newline| newline + 1);
+        let (part_header, rows) = contents.split_at(header_end);
+
+        let body: &[u8] = match &header {
+            None => {
+                header = Some(part_header.to_vec());
+                &contents
+            }
+            Some(header) if trim_header(header) == trim_header(part_header) => rows,
+            Some(_) => {
+                return Err(NetworkError::InvalidExport {
+                    path: part.display().to_string(),
+                    reason: "the header differs from the other parts of the split export"
+                        .to_string(),
+                })
+            }
+        };
+        file.write_all(body)
+            .map_err(|err| NetworkError::file(&tmp_path, err))?;
+        bytes += body.len() as u64;
+    }
+    file.sync_all()
+        .map_err(|err| NetworkError::file(&tmp_path, err))?;
+    drop(file);
+
+    std::fs::rename(&tmp_path, destination).map_err(|err| NetworkError::file(destination, err))?;
+    for part in parts {
+        std::fs::remove_file(part).map_err(|err| NetworkError::file(part, err))?;
+    }
+
+    Ok(bytes)
+}
+
+/// A header line without its byte order mark and line ending.
+fn trim_header(header: &[u8]) -> &[u8] {
+    let header: &[u8] = header.strip_prefix("\u{feff}".as_bytes()).unwrap_or(header);
+    header.strip_suffix(b"\n").map_or(header, |header| {
+        header.strip_suffix(b"\r").unwrap_or(header)
+    })
+}
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The name of the state file tracking completed downloads in the output directory
+const STATE_FILE_NAME: &str = ".download_state.json";
+
+/// A file that was completely downloaded.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct CompletedDownload {
+    bytes: u64,
+}
+
+/// Tracks which exported files of an output directory were completely downloaded,
+/// so an interrupted run can be resumed without trusting partially written files.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct DownloadState {
+    #[serde(skip)]
+    path: PathBuf,
+    completed: BTreeMap<String, CompletedDownload>,
+}
+
+impl DownloadState {
+    /// Loads the state of an output directory, starting empty if it has none.
+    pub fn load(output_directory: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let path: PathBuf = Path::new(output_directory).join(STATE_FILE_NAME);
+        let mut state: DownloadState = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => DownloadState::default(),
+            Err(err) => return Err(err.into()),
+        };
+        state.path = path;
+
+        Ok(state)
+    }
+
+    /// Whether `file_name` was completely downloaded and is still intact on disk.
+    pub fn is_complete(&self, file_name: &str) -> bool {
+        let Some(completed) = self.completed.get(file_name) else {
+            return false;
+        };
+        let path: PathBuf = self.path.with_file_name(file_name);
+
+        std::fs::metadata(path).is_ok_and(|metadata| metadata.len() == completed.bytes)
+    }
+
+    /// Records that `file_name` is being (re)downloaded and is no longer complete.
+    pub fn mark_incomplete(&mut self, file_name: &str) -> std::io::Result<()> {
+        if self.completed.remove(file_name).is_some() {
+            self.save()?;
+        }
+
+        Ok(())
+    }
+
+    /// Records that `file_name` was completely downloaded with `bytes` bytes.
+    pub fn mark_complete(&mut self, file_name: &str, bytes: u64) -> std::io::Result<()> {
+        self.completed
+            .insert(file_name.to_string(), CompletedDownload { bytes });
+        self.save()
+    }
+
+    /// Writes the state file, replacing the previous one atomically.
+    fn save(&self) -> std::io::Result<()> {
+        let json: String = serde_json::to_string_pretty(self)?;
+        let tmp_path: PathBuf = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, json)?;
+        std::fs::rename(tmp_path, &self.path)
+    }
+}
@@ -0,0 +1,352 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+
+use super::error::NetworkError;
+use super::progress::ProgressBar;
+use super::traffic::{build_response, HttpRequest, RequestBody, REDACTED_COOKIE};
+use crate::manifest::{rfc3339_utc, TOOL_VERSION};
+
+/// Traces the HTTP traffic of sessions for debugging.
+///
+/// Every request is logged to stderr as a line of `key=value` fields with its endpoint, status
+/// and timing. When a HAR path is set, every exchange is also kept, bodies included except those
+/// of exported files, and `save_har` writes them as a HAR 1.2 file that browser devtools can open.
+///
+/// Request headers aren't recorded, since the client adds them, the session cookie included,
+/// only when it sends the request. The cookies set by responses are replaced by
+/// `REDACTED_COOKIE`, so that a HAR file can be shared without the session.
+#[derive(Debug)]
+pub struct Tracer {
+    /// Where `save_har` writes the HAR file, or `None` to only log requests
+    har_path: Option<PathBuf>,
+    /// The HAR entries of the exchanges so far, in request order
+    entries: Mutex<Vec<serde_json::Value>>,
+}
+
+impl Tracer {
+    pub fn new(har_path: Option<PathBuf>) -> Self {
+        Tracer {
+            har_path,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Logs a line of `key=value` fields to stderr, above the progress bar `pb` if it's drawn.
+    pub(crate) fn log(&self, pb: &ProgressBar, fields: &str) {
+        pb.suspend(|| eprintln!("[debug] {}", fields));
+    }
+
+    /// Logs an exchange and, if a HAR file is kept, records it with its response body.
+    ///
+    /// Exported files are left out of the HAR file, which only records their size, so that they
+    /// are still streamed to disk by the caller.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - The URL of the server the request was sent to.
+    /// * `request` - The request.
+    /// * `started` - When the request was sent.
+    /// * `timer` - Started when the request was sent.
+    /// * `read_timeout` - The longest wait for each chunk of the response body, when it's read
+    ///   for the HAR file.
+    /// * `pb` - The progress bar the exchange is logged above.
+    /// * `result` - The response, or the error of the request.
+    ///
+    /// # Returns
+    ///
+    /// The response for the caller to consume, or the error of the request.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn trace(
+        &self,
+        base_url: &str,
+        request: &HttpRequest,
+        started: SystemTime,
+        timer: Instant,
+        read_timeout: Duration,
+        pb: &ProgressBar,
+        result: Result<reqwest::Response, NetworkError>,
+    ) -> Result<reqwest::Response, NetworkError> {
+        let url: String = format!("{}{}", base_url, request.path);
+        let wait: Duration = timer.elapsed();
+        let mut response: reqwest::Response = match result {
+            Ok(response) => response,
+            Err(err) => {
+                self.log(
+                    pb,
+                    &format!(
+                        "method={} endpoint={} status=error time_ms={} error={:?}",
+                        request.method,
+                        endpoint(&request.path),
+                        wait.as_millis(),
+                        err.to_string()
+                    ),
+                );
+                self.add_entry(har_entry(&url, request, started, wait, Err(&err)));
+                return Err(err);
+            }
+        };
+        self.log(
+            pb,
+            &format!(
+                "method={} endpoint={} status={} time_ms={}",
+                request.method,
+                endpoint(&request.path),
+                response.status().as_u16(),
+                wait.as_millis()
+            ),
+        );
+        if self.har_path.is_none() {
+            return Ok(response);
+        }
+
+        let status: reqwest::StatusCode = response.status();
+        let headers: reqwest::header::HeaderMap = response.headers().clone();
+        if endpoint(&request.path) == "tempfile" {
+            let har_response: HarResponse = HarResponse {
+                status,
+                headers: &headers,
+                body: HarBody::Omitted(response.content_length()),
+                receive: Duration::ZERO,
+            };
+            self.add_entry(har_entry(&url, request, started, wait, Ok(har_response)));
+            return Ok(response);
+        }
+
+        let mut body: Vec<u8> = Vec::new();
+        loop {
+            match tokio::time::timeout(read_timeout, response.chunk()).await {
+                Ok(Ok(Some(chunk))) => body.extend_from_slice(&chunk),
+                Ok(Ok(None)) => break,
+                Ok(Err(err)) => return Err(err.into()),
+                Err(_) => {
+                    return Err(NetworkError::Timeout {
+                        url,
+                        after: read_timeout,
+                    })
+                }
+            }
+        }
+        let body: Bytes = Bytes::from(body);
+        let har_response: HarResponse = HarResponse {
+            status,
+            headers: &headers,
+            body: HarBody::Recorded(&body),
+            receive: timer.elapsed() - wait,
+        };
+        self.add_entry(har_entry(&url, request, started, wait, Ok(har_response)));
+
+        let content_type: Option<&str> = headers
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok());
+        build_response(status.as_u16(), content_type, body)
+    }
+
+    fn add_entry(&self, entry: serde_json::Value) {
+        if self.har_path.is_some() {
+            self.entries.lock().unwrap().push(entry);
+        }
+    }
+
+    /// Writes the HAR file of the exchanges so far, if a HAR path is set.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(&Path))` - The path of the HAR file.
+    /// * `Ok(None)` - If no HAR file is kept.
+    /// * `Err(NetworkError::File)` - If the file can't be written.
+    pub fn save_har(&self) -> Result<Option<&Path>, NetworkError> {
+        let Some(path) = &self.har_path else {
+            return Ok(None);
+        };
+        let har: serde_json::Value = serde_json::json!({
+            "log": {
+                "version": "1.2",
+                "creator": {"name": env!("CARGO_PKG_NAME"), "version": TOOL_VERSION},
+                "pages": [],
+                "entries": *self.entries.lock().unwrap(),
+            }
+        });
+        let json: String = serde_json::to_string_pretty(&har).unwrap();
+        std::fs::write(path, json).map_err(|err| NetworkError::file(path, err))?;
+
+        Ok(Some(path))
+    }
+}
+
+/// The endpoint of a request, e.g. `tabdoc/categorical-filter` for a VizQL command or
+/// `bootstrapSession` for `/vizql/w/{workbook}/v/{view}/bootstrapSession/sessions/{id}`.
+fn endpoint(path: &str) -> &str {
+    let path: &str = path.split('?').next().unwrap_or_default();
+    if let Some((_, command)) = path.split_once("/commands/") {
+        return command;
+    }
+    match path.split_once("/vizql/w/") {
+        // Skip `{workbook}/v/{view}/`
+        Some((_, vizql_path)) => vizql_path.split('/').nth(3).unwrap_or(vizql_path),
+        None => path.trim_start_matches('/'),
+    }
+}
+
+/// A response as recorded in a HAR entry.
+struct HarResponse<'a> {
+    status: reqwest::StatusCode,
+    headers: &'a reqwest::header::HeaderMap,
+    body: HarBody<'a>,
+    /// How long reading the body took
+    receive: Duration,
+}
+
+/// The body of a response as recorded in a HAR entry.
+enum HarBody<'a> {
+    Recorded(&'a Bytes),
+    /// The body of an exported file, with its size if `Content-Length` announced it
+    Omitted(Option<u64>),
+}
+
+/// Builds the HAR entry of an exchange, with a `0` status if the request failed.
+fn har_entry(
+    url: &str,
+    request: &HttpRequest,
+    started: SystemTime,
+    wait: Duration,
+    response: Result<HarResponse, &NetworkError>,
+) -> serde_json::Value {
+    let name_values = |pairs: &mut dyn Iterator<Item = (&str, &str)>| -> Vec<serde_json::Value> {
+        pairs
+            .map(|(name, value)| serde_json::json!({"name": name, "value": value}))
+            .collect()
+    };
+    let query_string: Vec<serde_json::Value> = name_values(
+        &mut url
+            .split_once('?')
+            .map(|(_, query)| query)
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| pair.split_once('=').unwrap_or((pair, ""))),
+    );
+
+    let mut har_request: serde_json::Value = serde_json::json!({
+        "method": request.method,
+        "url": url,
+        "httpVersion": "HTTP/1.1",
+        "cookies": [],
+        "headers": [],
+        "queryString": query_string,
+        "headersSize": -1,
+        "bodySize": -1,
+        "comment": "Request headers, the session cookie included, aren't recorded",
+    });
+    let post_data = |mime_type: &str, fields: &[(String, String)]| -> serde_json::Value {
+        serde_json::json!({
+            "mimeType": mime_type,
+            "params": name_values(
+                &mut fields.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+            ),
+            "text": "",
+        })
+    };
+    match &request.body {
+        RequestBody::Empty => {}
+        RequestBody::Form(fields) => {
+            har_request["postData"] = post_data("application/x-www-form-urlencoded", fields)
+        }
+        RequestBody::Multipart(fields) => {
+            har_request["postData"] = post_data("multipart/form-data", fields)
+        }
+    }
+
+    let (har_response, receive): (serde_json::Value, Duration) = match response {
+        Ok(response) => {
+            let mime_type: &str = response
+                .headers
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            // HAR uses -1 for unknown sizes
+            let (content, body_size): (serde_json::Value, i64) = match response.body {
+                HarBody::Recorded(body) => (
+                    serde_json::json!({
+                        "size": body.len(),
+                        "mimeType": mime_type,
+                        "text": String::from_utf8_lossy(body),
+                    }),
+                    body.len() as i64,
+                ),
+                HarBody::Omitted(size) => {
+                    let size: i64 = size.map_or(-1, |size| size as i64);
+                    (
+                        serde_json::json!({
+                            "size": size,
+                            "mimeType": mime_type,
+                            "comment": "The exported file isn't recorded",
+                        }),
+                        size,
+                    )
+                }
+            };
+            (
+                serde_json::json!({
+                    "status": response.status.as_u16(),
+                    "statusText": response.status.canonical_reason().unwrap_or_default(),
+                    "httpVersion": "HTTP/1.1",
+                    "cookies": [],
+                    "headers": name_values(&mut response.headers.iter().map(|(name, value)| {
+                        if name == reqwest::header::SET_COOKIE {
+                            (name.as_str(), REDACTED_COOKIE)
+                        } else {
+                            (name.as_str(), value.to_str().unwrap_or_default())
+                        }
+                    })),
+                    "content": content,
+                    "redirectURL": "",
+                    "headersSize": -1,
+                    "bodySize": body_size,
+                }),
+                response.receive,
+            )
+        }
+        Err(err) => (
+            serde_json::json!({
+                "status": 0,
+                "statusText": "",
+                "httpVersion": "HTTP/1.1",
+                "cookies": [],
+                "headers": [],
+                "content": {"size": 0, "mimeType": ""},
+                "redirectURL": "",
+                "headersSize": -1,
+                "bodySize": -1,
+                "comment": err.to_string(),
+            }),
+            Duration::ZERO,
+        ),
+    };
+
+    serde_json::json!({
+        "startedDateTime": iso8601_millis(started),
+        "time": (wait + receive).as_secs_f64() * 1000.0,
+        "request": har_request,
+        "response": har_response,
+        "cache": {},
+        "timings": {
+            "send": 0,
+            "wait": wait.as_secs_f64() * 1000.0,
+            "receive": receive.as_secs_f64() * 1000.0,
+        },
+    })
+}
+
+/// Formats a time as an ISO 8601 UTC timestamp with milliseconds, e.g. `2024-03-14T09:26:53.125Z`.
+fn iso8601_millis(time: SystemTime) -> String {
+    let seconds: String = rfc3339_utc(time);
+    let millis: u32 = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_millis();
+
+    format!("{}.{:03}Z", seconds.trim_end_matches('Z'), millis)
+}
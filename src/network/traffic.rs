@@ -0,0 +1,433 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use bytes::Bytes;
+use tokio::io::AsyncWriteExt;
+
+use super::error::NetworkError;
+
+/// The placeholder replacing session ids in recorded traffic
+pub const REDACTED_SESSION_ID: &str = "REDACTED-SESSION-ID";
+
+/// The placeholder replacing the cookies set by the server in recorded traffic
+pub const REDACTED_COOKIE: &str = "REDACTED-COOKIE";
+
+/// The body of an HTTP request made by a session.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", content = "fields", rename_all = "snake_case")]
+pub enum RequestBody {
+    Empty,
+    /// `application/x-www-form-urlencoded` fields
+    Form(Vec<(String, String)>),
+    /// `multipart/form-data` text fields
+    Multipart(Vec<(String, String)>),
+}
+
+/// An HTTP request made by a session.
+///
+/// Requests are kept as data rather than as `reqwest::RequestBuilder`s so that they can be
+/// rebuilt when retried, and recorded or replayed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub method: String,
+    /// The path and query of the request, relative to the server base URL
+    pub path: String,
+    pub body: RequestBody,
+}
+
+impl HttpRequest {
+    pub fn get(path: String) -> Self {
+        HttpRequest {
+            method: "GET".to_string(),
+            path,
+            body: RequestBody::Empty,
+        }
+    }
+
+    pub fn post(path: String, body: RequestBody) -> Self {
+        HttpRequest {
+            method: "POST".to_string(),
+            path,
+            body,
+        }
+    }
+
+    /// Builds the request for sending with `client` to the server at `base_url`.
+    pub fn build(&self, client: &reqwest::Client, base_url: &str) -> reqwest::RequestBuilder {
+        let url: String = format!("{}{}", base_url, self.path);
+        let request: reqwest::RequestBuilder = match self.method.as_str() {
+            "POST" => client.post(url),
+            _ => client.get(url),
+        };
+
+        match &self.body {
+            RequestBody::Empty => request,
+            RequestBody::Form(fields) => request.form(fields),
+            RequestBody::Multipart(fields) => request.multipart(
+                fields
+                    .iter()
+                    .fold(reqwest::multipart::Form::new(), |form, (name, value)| {
+                        form.text(name.clone(), value.clone())
+                    }),
+            ),
+        }
+    }
+
+    /// Returns a copy of the request with every occurrence of `secret` replaced by `replacement`.
+    fn redact(&self, secret: &str, replacement: &str) -> Self {
+        let redact_fields = |fields: &[(String, String)]| -> Vec<(String, String)> {
+            fields
+                .iter()
+                .map(|(name, value)| (name.clone(), value.replace(secret, replacement)))
+                .collect()
+        };
+
+        HttpRequest {
+            method: self.method.clone(),
+            path: self.path.replace(secret, replacement),
+            body: match &self.body {
+                RequestBody::Empty => RequestBody::Empty,
+                RequestBody::Form(fields) => RequestBody::Form(redact_fields(fields)),
+                RequestBody::Multipart(fields) => RequestBody::Multipart(redact_fields(fields)),
+            },
+        }
+    }
+}
+
+/// A response as recorded on disk.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct RecordedResponse {
+    status: u16,
+    /// The response headers, in order, with `Set-Cookie` values masked
+    headers: Vec<(String, String)>,
+    /// The name of the file holding the raw response body, next to the exchange file
+    body_file: String,
+}
+
+impl RecordedResponse {
+    /// Builds the recorded response with its headers and `body`.
+    fn build(&self, body: Bytes) -> Result<reqwest::Response, NetworkError> {
+        let response: http::Response<Bytes> = self
+            .headers
+            .iter()
+            .fold(
+                http::Response::builder().status(self.status),
+                |response, (name, value)| response.header(name.as_str(), value.as_str()),
+            )
+            .body(body)
+            .map_err(|err| {
+                NetworkError::Recording(format!("Invalid recorded response: {}", err))
+            })?;
+
+        Ok(reqwest::Response::from(response))
+    }
+}
+
+/// Builds a response that didn't come from the network.
+pub fn build_response(
+    status: u16,
+    content_type: Option<&str>,
+    body: Bytes,
+) -> Result<reqwest::Response, NetworkError> {
+    let mut response = http::Response::builder().status(status);
+    if let Some(content_type) = content_type {
+        response = response.header(reqwest::header::CONTENT_TYPE, content_type);
+    }
+    let response: http::Response<Bytes> = response
+        .body(body)
+        .map_err(|err| NetworkError::Recording(format!("Invalid recorded response: {}", err)))?;
+
+    Ok(reqwest::Response::from(response))
+}
+
+/// A request/response pair as recorded on disk.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Exchange {
+    request: HttpRequest,
+    response: RecordedResponse,
+}
+
+/// Whether the HTTP traffic of sessions is recorded to, or replayed from, a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrafficMode {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+/// Records or replays the HTTP traffic of sessions.
+///
+/// Recorded exchanges are written as numbered JSON files (`0001.json`, ...) holding the request
+/// and the response status and headers, with the raw response body streamed to a file of the same
+/// number (`0001.body`). Session ids are replaced by `REDACTED_SESSION_ID` everywhere, and cookies
+/// set by the server by `REDACTED_COOKIE`, since replayed sessions don't send cookies. Replayed requests are answered with the first unused
+/// recorded exchange of an identical request, so a replayed run sees the same responses in the
+/// same order as the recorded one without touching the network.
+#[derive(Debug)]
+pub struct Traffic {
+    mode: Mode,
+}
+
+#[derive(Debug)]
+enum Mode {
+    Record {
+        directory: PathBuf,
+        recording: Mutex<Recording>,
+    },
+    Replay {
+        directory: PathBuf,
+        /// The recorded exchanges, removed once replayed
+        exchanges: Mutex<Vec<Exchange>>,
+    },
+}
+
+#[derive(Debug, Default)]
+struct Recording {
+    /// The session ids to redact
+    session_ids: Vec<String>,
+    /// The number of exchanges started so far, which numbers their files
+    started: usize,
+    /// The exchange and body files written so far
+    files: Vec<PathBuf>,
+}
+
+impl Traffic {
+    /// Prepares recording to, or replaying from, a directory.
+    pub fn open(mode: &TrafficMode) -> Result<Self, NetworkError> {
+        let mode: Mode = match mode {
+            TrafficMode::Record(directory) => {
+                std::fs::create_dir_all(directory).map_err(|err| {
+                    NetworkError::Recording(format!(
+                        "Failed to create {}: {}",
+                        directory.display(),
+                        err
+                    ))
+                })?;
+                // Start from a clean recording so stale exchanges are never replayed
+                let mut paths: Vec<PathBuf> = exchange_paths(directory, "json")?;
+                paths.extend(exchange_paths(directory, "body")?);
+                for path in paths {
+                    std::fs::remove_file(&path).map_err(|err| {
+                        NetworkError::Recording(format!(
+                            "Failed to remove {}: {}",
+                            path.display(),
+                            err
+                        ))
+                    })?;
+                }
+                Mode::Record {
+                    directory: directory.clone(),
+                    recording: Mutex::new(Recording::default()),
+                }
+            }
+            TrafficMode::Replay(directory) => Mode::Replay {
+                directory: directory.clone(),
+                exchanges: Mutex::new(read_exchanges(directory)?),
+            },
+        };
+
+        Ok(Traffic { mode })
+    }
+
+    /// Whether responses come from a recording instead of the network.
+    pub fn is_replay(&self) -> bool {
+        matches!(self.mode, Mode::Replay { .. })
+    }
+
+    /// Registers a session id to redact from recorded traffic, including already recorded files.
+    pub fn redact_session_id(&self, session_id: &str) -> Result<(), NetworkError> {
+        let Mode::Record { recording, .. } = &self.mode else {
+            return Ok(());
+        };
+        let mut recording = recording.lock().unwrap();
+        recording.session_ids.push(session_id.to_string());
+
+        for path in &recording.files {
+            redact_file(path, &[session_id.to_string()])?;
+        }
+
+        Ok(())
+    }
+
+    /// Records an exchange, returning an equivalent response for the caller to consume.
+    ///
+    /// The body is written to disk as it's received, then read back for the caller.
+    pub async fn record(
+        &self,
+        request: &HttpRequest,
+        mut response: reqwest::Response,
+    ) -> Result<reqwest::Response, NetworkError> {
+        let Mode::Record {
+            directory,
+            recording,
+        } = &self.mode
+        else {
+            return Ok(response);
+        };
+
+        let number: usize = {
+            let mut recording = recording.lock().unwrap();
+            recording.started += 1;
+            recording.started
+        };
+        let path: PathBuf = directory.join(format!("{:04}.json", number));
+        let body_path: PathBuf = directory.join(format!("{:04}.body", number));
+
+        let mut exchange: Exchange = Exchange {
+            request: request.clone(),
+            response: RecordedResponse {
+                status: response.status().as_u16(),
+                headers: response
+                    .headers()
+                    .iter()
+                    .map(|(name, value)| {
+                        let value: String = if name == reqwest::header::SET_COOKIE {
+                            REDACTED_COOKIE.to_string()
+                        } else {
+                            String::from_utf8_lossy(value.as_bytes()).to_string()
+                        };
+                        (name.to_string(), value)
+                    })
+                    .collect(),
+                body_file: format!("{:04}.body", number),
+            },
+        };
+
+        let recording_error = |err: std::io::Error| {
+            NetworkError::Recording(format!("Failed to write {}: {}", body_path.display(), err))
+        };
+        let mut file: tokio::fs::File = tokio::fs::File::create(&body_path)
+            .await
+            .map_err(recording_error)?;
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await.map_err(recording_error)?;
+        }
+        file.flush().await.map_err(recording_error)?;
+        drop(file);
+        let body: Bytes = Bytes::from(std::fs::read(&body_path).map_err(recording_error)?);
+        // The caller gets the response as sent, so the ids are only redacted once it's read back
+        let caller_response: reqwest::Response = exchange.response.build(body)?;
+
+        let mut recording = recording.lock().unwrap();
+        for session_id in &recording.session_ids {
+            exchange.request = exchange.request.redact(session_id, REDACTED_SESSION_ID);
+            for (_, value) in &mut exchange.response.headers {
+                *value = value.replace(session_id, REDACTED_SESSION_ID);
+            }
+        }
+        redact_file(&body_path, &recording.session_ids)?;
+        write_file(&path, serde_json::to_string_pretty(&exchange).unwrap())?;
+        recording.files.push(path);
+        recording.files.push(body_path);
+
+        Ok(caller_response)
+    }
+
+    /// Answers a request from the recording.
+    pub fn replay(&self, request: &HttpRequest) -> Result<reqwest::Response, NetworkError> {
+        let Mode::Replay {
+            directory,
+            exchanges,
+        } = &self.mode
+        else {
+            return Err(NetworkError::Recording("Not replaying traffic".to_string()));
+        };
+
+        let mut exchanges = exchanges.lock().unwrap();
+        let position: usize = exchanges
+            .iter()
+            .position(|exchange| exchange.request == *request)
+            .ok_or_else(|| {
+                NetworkError::Recording(format!(
+                    "No recorded response left for {} {}",
+                    request.method, request.path
+                ))
+            })?;
+
+        let response: RecordedResponse = exchanges.remove(position).response;
+        let body_path: PathBuf = directory.join(&response.body_file);
+        let body: Vec<u8> = std::fs::read(&body_path).map_err(|err| {
+            NetworkError::Recording(format!("Failed to read {}: {}", body_path.display(), err))
+        })?;
+        response.build(Bytes::from(body))
+    }
+}
+
+/// Lists the recorded files of a directory with an extension, in recording order.
+fn exchange_paths(directory: &Path, extension: &str) -> Result<Vec<PathBuf>, NetworkError> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(directory)
+        .map_err(|err| {
+            NetworkError::Recording(format!("Failed to read {}: {}", directory.display(), err))
+        })?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|path_extension| path_extension == extension)
+        })
+        .collect();
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Reads the recorded exchanges of a directory, in recording order.
+fn read_exchanges(directory: &Path) -> Result<Vec<Exchange>, NetworkError> {
+    exchange_paths(directory, "json")?
+        .iter()
+        .map(|path| {
+            serde_json::from_str(&read_file(path)?).map_err(|err| {
+                NetworkError::Recording(format!("Invalid recording {}: {}", path.display(), err))
+            })
+        })
+        .collect()
+}
+
+fn read_file(path: &Path) -> Result<String, NetworkError> {
+    std::fs::read_to_string(path).map_err(|err| {
+        NetworkError::Recording(format!("Failed to read {}: {}", path.display(), err))
+    })
+}
+
+fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), NetworkError> {
+    std::fs::write(path, contents).map_err(|err| {
+        NetworkError::Recording(format!("Failed to write {}: {}", path.display(), err))
+    })
+}
+
+/// Replaces every occurrence of `session_ids` in a recorded file by `REDACTED_SESSION_ID`.
+fn redact_file(path: &Path, session_ids: &[String]) -> Result<(), NetworkError> {
+    let contents: Vec<u8> = std::fs::read(path).map_err(|err| {
+        NetworkError::Recording(format!("Failed to read {}: {}", path.display(), err))
+    })?;
+    let redacted: Vec<u8> = session_ids
+        .iter()
+        .fold(contents.clone(), |contents, session_id| {
+            replace_bytes(
+                &contents,
+                session_id.as_bytes(),
+                REDACTED_SESSION_ID.as_bytes(),
+            )
+        });
+    if redacted != contents {
+        write_file(path, redacted)?;
+    }
+
+    Ok(())
+}
+
+/// Replaces every occurrence of `from` in `bytes` by `to`.
+fn replace_bytes(bytes: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    if from.is_empty() {
+        return bytes.to_vec();
+    }
+    let mut replaced: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut rest: &[u8] = bytes;
+    while let Some(position) = rest.windows(from.len()).position(|window| window == from) {
+        replaced.extend_from_slice(&rest[..position]);
+        replaced.extend_from_slice(to);
+        rest = &rest[position + from.len()..];
+    }
+    replaced.extend_from_slice(rest);
+
+    replaced
+}
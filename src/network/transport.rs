@@ -0,0 +1,92 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::error::NetworkError;
+
+/// The default `User-Agent` header of every request
+pub const DEFAULT_USER_AGENT: &str =
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// How HTTP requests reach the Tableau server.
+///
+/// Every session builds its client from the same transport, so the proxy, timeouts, user agent
+/// and trusted certificates apply uniformly to every request made by the network module.
+#[derive(Debug, Clone)]
+pub struct TransportConfig {
+    /// The proxy every request goes through, instead of the proxy of the environment
+    pub proxy: Option<reqwest::Proxy>,
+    /// The longest wait for a connection to the server
+    pub connect_timeout: Duration,
+    /// The longest wait for the response headers, or for the next chunk of a response body
+    pub read_timeout: Duration,
+    pub user_agent: String,
+    /// Root certificates trusted in addition to the system ones
+    pub ca_certificates: Vec<reqwest::Certificate>,
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        TransportConfig {
+            proxy: None,
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(60),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            ca_certificates: Vec::new(),
+        }
+    }
+}
+
+impl TransportConfig {
+    /// Routes every request through a proxy, e.g. `http://proxy.example.edu:3128`.
+    pub fn set_proxy(&mut self, url: &str) -> Result<(), NetworkError> {
+        let proxy: reqwest::Proxy = reqwest::Proxy::all(url)
+            .map_err(|err| NetworkError::Config(format!("Invalid proxy URL {}: {}", url, err)))?;
+        self.proxy = Some(proxy);
+
+        Ok(())
+    }
+
+    /// Trusts the root certificates of a PEM file, which may hold several certificates.
+    pub fn add_ca_certificates(&mut self, path: &Path) -> Result<(), NetworkError> {
+        let pem: Vec<u8> = std::fs::read(path).map_err(|err| {
+            NetworkError::Config(format!("Failed to read {}: {}", path.display(), err))
+        })?;
+        let certificates: Vec<reqwest::Certificate> = reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|err| {
+            NetworkError::Config(format!(
+                "Invalid PEM certificate {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        if certificates.is_empty() {
+            return Err(NetworkError::Config(format!(
+                "No PEM certificate found in {}",
+                path.display()
+            )));
+        }
+        self.ca_certificates.extend(certificates);
+
+        Ok(())
+    }
+
+    /// Builds an HTTP client using this transport, keeping its cookies in `cookies`.
+    pub fn build_client(
+        &self,
+        cookies: Arc<reqwest::cookie::Jar>,
+    ) -> Result<reqwest::Client, NetworkError> {
+        let mut builder: reqwest::ClientBuilder = reqwest::Client::builder()
+            .cookie_provider(cookies)
+            .connect_timeout(self.connect_timeout)
+            .user_agent(self.user_agent.as_str());
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        for certificate in &self.ca_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+
+        Ok(builder.build()?)
+    }
+}
@@ -1,64 +1,173 @@
+//! This module contains functions for parsing CSV files containing course information.
+//! It provides functions to parse individual CSV files and directories containing multiple CSV files.
+//! Parsed data is written to another CSV file.
+//!
+//! The main functions in this module are:
+//! - `parse_csv_file`: Parses a single CSV file and writes the parsed data to another CSV file.
+//! - `parse_csv_directory`: Parses a directory containing multiple CSV files and writes the parsed data to corresponding output CSV files.
+//!   The provenance of the input files listed in their `manifest.json` is carried into a `manifest.json` of the output files.
+//!
+//! Parsed files have one column per grade of the `GradeView` of the export: `A`, `A-`, `B+`, ...
+//! for the expanded view, and `A (all)`, `B (all)`, ... for the condensed view. The view of each
+//! file is read from the `[Parameters].[Parameter 1]` value of its manifest entry, unless it's
+//! given explicitly.
+//!
+//! Each parsed row is one section of a course in a semester, unless a `Rollup` sums the sections
+//! of a course per semester or over the whole file.
+//!
+//! The module also defines two structs:
+//! - `CourseInfo`: Represents the information of a course.
+//! - `CourseInfoTokenized`: Represents the tokenized information of a course.
+//!
+//! It uses the `std::collections::HashMap` struct for storing and manipulating course information.
+//!
+//! Example usage:
+//! ```
+//! use ut_grade_parser::parse::{parse_csv_directory, parse_csv_file, Rollup};
+//! use ut_grade_parser::GradeView;
+//!
+//! let directory = std::env::temp_dir().join(format!("ut_grade_parser_doc_parse_{}", std::process::id()));
+//! std::fs::create_dir_all(directory.join("export"))?;
+//! std::fs::write(
+//!     directory.join("export").join("grade_distributions_2022-2023.csv"),
+//!     "Semester,Section,Department,Department Code,Course Number,Course Title,Course Full Title,Letter Grade,Count of letter grade\n\
+//!      Fall 2022,12345,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,A,10\n\
+//!      Fall 2022,67890,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,A,20\n",
+//! )?;
+//! let export = directory.join("export");
+//! let parsed = directory.join("parsed");
+//!
+//! // Parse a single CSV file, summing the sections of each course per semester
+//! parse_csv_file(
+//!     export.join("grade_distributions_2022-2023.csv").to_str().unwrap(),
+//!     directory.join("courses.csv").to_str().unwrap(),
+//!     GradeView::Expanded,
+//!     Some(Rollup::Semester),
+//! )?;
+//!
+//! // Parse a directory containing multiple CSV files of the condensed grade view, with one row
+//! // per section
+//! parse_csv_directory(
+//!     export.to_str().unwrap(),
+//!     parsed.to_str().unwrap(),
+//!     Some(GradeView::Condensed),
+//!     None,
+//! )?;
+//! assert!(parsed.join("grade_distributions_2022-2023.csv").exists());
+//! # std::fs::remove_dir_all(&directory)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::grade::{Grade, GradeDistribution, GradeView};
+use crate::manifest::{FileDigest, Manifest, ManifestEntry, TOOL_VERSION};
+use crate::term::Term;
+
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
-/// This module contains functions for parsing CSV files containing course information.
-/// It provides functions to parse individual CSV files and directories containing multiple CSV files.
-/// Parsed data is written to another CSV file.
-///
-/// The main functions in this module are:
-/// - `parse_csv_file`: Parses a single CSV file and writes the parsed data to another CSV file.
-/// - `parse_csv_directory`: Parses a directory containing multiple CSV files and writes the parsed data to corresponding output CSV files.
-///
-/// The module also defines two structs:
-/// - `CourseInfo`: Represents the information of a course.
-/// - `CourseInfoTokenized`: Represents the tokenized information of a course.
-///
-/// The module uses the `encoding_rs` crate for decoding UTF-16LE encoded files.
-/// It also uses the `std::collections::HashMap` struct for storing and manipulating course information.
-///
-/// Example usage:
-/// ```
-/// use parse::{parse_csv_file, parse_csv_directory};
-///
-/// // Parse a single CSV file
-/// parse_csv_file("input.csv", "output.csv");
-///
-/// // Parse a directory containing multiple CSV files
-/// parse_csv_directory("input_directory", "output_directory");
-/// ```
 
-const CSV_HEADER: &str = "Semester\tSection\tDepartment\tDepartment Code\tCourse Number\tCourse Title\tCourse Full Title\tA\tA-\tB+\tB\tB-\tC+\tC\tC-\tD+\tD\tD-\tF\tOther";
+/// The course columns of a parsed file, followed by one column per grade. The term code is the
+/// UT `CCYYS` code of the semester, e.g. `20229` for Fall 2022, which sorts chronologically.
+const CSV_HEADER: &str = "Semester\tTerm Code\tSection\tDepartment\tDepartment Code\tCourse Number\tCourse Title\tCourse Full Title";
+
+/// The errors of `parse_csv_directory`.
+#[derive(Debug)]
+pub enum ParseError {
+    /// The input directory can't be read, or the output directory or its manifest can't be
+    /// written.
+    Directory(String),
+    /// Some files failed to parse, while the others were parsed.
+    Files(Vec<FileError>),
+}
+
+/// A file of a directory that failed to parse.
+#[derive(Debug)]
+pub struct FileError {
+    /// The path of the input file
+    pub path: String,
+    /// Why the file failed to parse, starting with its path
+    pub error: Box<dyn std::error::Error>,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Directory(message) => write!(f, "{}", message),
+            ParseError::Files(errors) => {
+                write!(f, "Failed to parse {} CSV file(s):", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  {}", error.error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
 
-const GRADE_NAMES: [&str; 13] = [
-    "A", "A-", "B+", "B", "B-", "C+", "C", "C-", "D+", "D", "D-", "F", "Other",
-];
+impl std::error::Error for ParseError {}
+
+/// How the rows of a course are summed into one row of a parsed file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Rollup {
+    /// One row per course and semester, summing its sections
+    Semester,
+    /// One row per course, summing its sections in every semester of the file
+    Course,
+}
 
 /// Represents the information of a course.
+///
+/// The semester and section are `None` when a `Rollup` summed several of them.
 #[derive(Serialize, Deserialize, Debug)]
 struct CourseInfo {
-    semester: String,
-    section: u32,
+    semester: Option<Term>,
+    section: Option<u32>,
     department: String,
     department_code: String,
     course_number: String,
     course_title: String,
     course_full_title: String,
-    grade: HashMap<String, u16>,
+    grade: GradeDistribution,
+}
+
+/// The columns identifying the row of a parsed file that an exported row is counted in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RowKey {
+    semester: Option<Term>,
+    section: Option<u32>,
+    department_code: String,
+    course_number: String,
+    course_title: String,
 }
 
 /// Represents the tokenized information of a course.
 #[derive(Serialize, Deserialize, Debug)]
 struct CourseInfoTokenized {
-    semester: String,
+    semester: Term,
     section: u32,
     department: String,
     department_code: String,
     course_number: String,
     course_title: String,
     course_full_title: String,
-    grade: String,
-    grade_count: u16,
+    grade: Grade,
+    grade_count: u32,
+}
+
+impl CourseInfoTokenized {
+    /// Returns the key of the parsed row this row is counted in: its section, or its course
+    /// in the semester or the file when rolled up.
+    fn row_key(&self, rollup: Option<Rollup>) -> RowKey {
+        RowKey {
+            semester: (rollup != Some(Rollup::Course)).then_some(self.semester),
+            section: rollup.is_none().then_some(self.section),
+            department_code: self.department_code.clone(),
+            course_number: self.course_number.clone(),
+            course_title: self.course_title.clone(),
+        }
+    }
 }
 
 /// Parses the input string and returns a `CourseInfoTokenized` struct.
@@ -70,21 +179,28 @@ struct CourseInfoTokenized {
 /// # Returns
 ///
 /// * `Ok(CourseInfoTokenized)` - If the input string is successfully parsed.
-/// * `Err(())` - If the input string cannot be parsed.
+/// * `Err(Box<dyn std::error::Error>)` - If the input string cannot be parsed, e.g. an unknown grade
+///   or a count that isn't a number.
 fn parse_course_info(
     record: Result<csv::StringRecord, csv::Error>,
 ) -> Result<CourseInfoTokenized, Box<dyn std::error::Error>> {
     let tokens = record?;
     if tokens.len() == 9 {
-        let semester: String = tokens[0].to_string();
-        let section: u32 = tokens[1].parse::<u32>().unwrap();
+        let semester: Term = tokens[0].parse()?;
+        let section: u32 = tokens[1]
+            .trim()
+            .parse()
+            .map_err(|err| format!("Invalid section {:?}: {}", &tokens[1], err))?;
         let department: String = tokens[2].to_string();
         let department_code: String = tokens[3].to_string();
         let course_number: String = tokens[4].trim().to_string();
         let course_title: String = tokens[5].to_string();
         let course_full_title: String = tokens[6].to_string();
-        let grade: String = tokens[7].to_string();
-        let grade_count: u16 = tokens[8].replace(",", "").parse::<u16>().unwrap();
+        let grade: Grade = tokens[7].parse()?;
+        let grade_count: u32 =
+            tokens[8].trim().replace(",", "").parse().map_err(|err| {
+                format!("Invalid count of letter grade {:?}: {}", &tokens[8], err)
+            })?;
 
         Ok(CourseInfoTokenized {
             semester,
@@ -108,88 +224,124 @@ fn parse_course_info(
 
 /// Parses a CSV file containing course information and writes the parsed data to another CSV file.
 ///
+/// Rows are written in the order their section, or their course if rolled up, first appears in
+/// the input file, and the counts of every input row of a parsed row are summed.
+///
 /// # Arguments
 ///
 /// * `input_file` - The path to the input CSV file.
 /// * `output_file` - The path to the output CSV file.
+/// * `grade_view` - The grade view of the export, which decides the grade columns.
+/// * `rollup` - How to sum the sections of a course, or `None` for one row per section.
+///
+/// # Returns
+///
+/// * `Err(Box<dyn std::error::Error>)` - If a row can't be parsed, e.g. it has a grade that isn't
+///   a column of `grade_view`, or the output file can't be written.
 ///
 /// # Example
 ///
 /// ```
-/// use parse::parse_csv_file;
+/// use ut_grade_parser::parse::parse_csv_file;
+/// use ut_grade_parser::GradeView;
+///
+/// let directory = std::env::temp_dir().join(format!("ut_grade_parser_doc_parse_file_{}", std::process::id()));
+/// std::fs::create_dir_all(&directory)?;
+/// let input = directory.join("export.csv");
+/// let output = directory.join("parsed.csv");
+/// std::fs::write(
+///     &input,
+///     "Semester,Section,Department,Department Code,Course Number,Course Title,Course Full Title,Letter Grade,Count of letter grade\n\
+///      Spring 2023,54321,Mathematics,M,408C,CALCULUS,M 408C CALCULUS,A,7\n",
+/// )?;
+///
+/// parse_csv_file(
+///     input.to_str().unwrap(),
+///     output.to_str().unwrap(),
+///     GradeView::Condensed,
+///     None,
+/// )?;
 ///
-/// parse_csv_file("input.csv", "output.csv");
+/// assert_eq!(
+///     std::fs::read_to_string(&output)?,
+///     "Semester\tTerm Code\tSection\tDepartment\tDepartment Code\tCourse Number\tCourse Title\tCourse Full Title\tA (all)\tB (all)\tC (all)\tD (all)\tF\tOther\n\
+///      Spring 2023\t20232\t54321\tMathematics\tM\t408C\tCALCULUS\tM 408C CALCULUS\t7\t0\t0\t0\t0\t0",
+/// );
+/// # std::fs::remove_dir_all(&directory)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
 pub fn parse_csv_file(
     input_file: &str,
     output_file: &str,
+    grade_view: GradeView,
+    rollup: Option<Rollup>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut csv_reader = csv::Reader::from_path(input_file)?;
-    let mut course_info_map: HashMap<String, CourseInfo> = HashMap::new();
-
-    for record in csv_reader.records() {
-        let course_info = parse_course_info(record)?;
-        let course_full_title: String = course_info.course_full_title.clone();
-
-        if let Some(existing_course_info) = course_info_map.get_mut(&course_full_title) {
-            existing_course_info
-                .grade
-                .insert(course_info.grade, course_info.grade_count);
-        } else {
-            let mut new_course_info = CourseInfo {
-                semester: course_info.semester,
-                section: course_info.section,
+    let mut csv_reader = csv::Reader::from_path(input_file)
+        .map_err(|err| format!("Failed to read {}: {}", input_file, err))?;
+    let mut courses: Vec<CourseInfo> = Vec::new();
+    let mut course_indices: HashMap<RowKey, usize> = HashMap::new();
+    let grade_columns: &[Grade] = grade_view.columns();
+
+    for (row, record) in csv_reader.records().enumerate() {
+        let course_info = parse_course_info(record)
+            .map_err(|err| format!("{}, row {}: {}", input_file, row + 1, err))?;
+        if !grade_columns.contains(&course_info.grade) {
+            return Err(format!(
+                "{}, row {}: The grade {} isn't a column of the {} grade view",
+                input_file,
+                row + 1,
+                course_info.grade,
+                grade_view
+            )
+            .into());
+        }
+        let row_key: RowKey = course_info.row_key(rollup);
+
+        let index: usize = *course_indices.entry(row_key.clone()).or_insert_with(|| {
+            courses.push(CourseInfo {
+                semester: row_key.semester,
+                section: row_key.section,
                 department: course_info.department,
                 department_code: course_info.department_code,
                 course_number: course_info.course_number,
                 course_title: course_info.course_title,
                 course_full_title: course_info.course_full_title,
-                grade: {
-                    let mut grade_map = HashMap::new();
-                    grade_map.insert("A".to_string(), 0);
-                    grade_map.insert("A-".to_string(), 0);
-                    grade_map.insert("B+".to_string(), 0);
-                    grade_map.insert("B".to_string(), 0);
-                    grade_map.insert("B-".to_string(), 0);
-                    grade_map.insert("C+".to_string(), 0);
-                    grade_map.insert("C".to_string(), 0);
-                    grade_map.insert("C-".to_string(), 0);
-                    grade_map.insert("D+".to_string(), 0);
-                    grade_map.insert("D".to_string(), 0);
-                    grade_map.insert("D-".to_string(), 0);
-                    grade_map.insert("F".to_string(), 0);
-                    grade_map.insert("Other".to_string(), 0);
-                    grade_map
-                },
-            };
-
-            // Edge-case: UT doesn't have an A+ grade but it's in the data
-            if course_info.grade == "A+" {
-                // Add the A+ grade to the A grade
-                let a_grade_count =
-                    course_info.grade_count + new_course_info.grade.get("A").unwrap();
-                new_course_info.grade.insert("A".to_string(), a_grade_count);
-            } else {
-                new_course_info
-                    .grade
-                    .insert(course_info.grade, course_info.grade_count);
-            }
-            course_info_map.insert(course_full_title, new_course_info);
-        }
+                grade: GradeDistribution::default(),
+            });
+            courses.len() - 1
+        });
+
+        courses[index]
+            .grade
+            .record(course_info.grade, course_info.grade_count);
     }
 
-    let mut csv_output_file: File =
-        File::create(output_file).expect(&format!("Failed to create output file: {}", output_file));
+    let mut csv_output_file: File = File::create(output_file)
+        .map_err(|err| format!("Failed to create output file {}: {}", output_file, err))?;
 
+    let header: String = std::iter::once(CSV_HEADER.to_string())
+        .chain(grade_columns.iter().map(|&grade| grade_view.label(grade)))
+        .collect::<Vec<String>>()
+        .join("\t");
     csv_output_file
-        .write_all(CSV_HEADER.as_bytes())
-        .expect(&format!("Failed to write header to file: {}", output_file));
+        .write_all(header.as_bytes())
+        .map_err(|err| format!("Failed to write header to file {}: {}", output_file, err))?;
 
-    for (_, course_info) in course_info_map.iter() {
+    for course_info in courses.iter() {
         let mut output_line: String = format!(
-            "\n{}\t{}\t{}\t{}\t{}\t{}\t{}",
-            course_info.semester,
-            course_info.section,
+            "\n{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            course_info
+                .semester
+                .map(|semester| semester.to_string())
+                .unwrap_or_default(),
+            course_info
+                .semester
+                .map(|semester| semester.code().to_string())
+                .unwrap_or_default(),
+            course_info
+                .section
+                .map(|section| section.to_string())
+                .unwrap_or_default(),
             course_info.department,
             course_info.department_code,
             course_info.course_number,
@@ -197,14 +349,18 @@ pub fn parse_csv_file(
             course_info.course_full_title
         );
 
-        for grade_name in GRADE_NAMES.iter() {
-            let grade_count = course_info.grade.get(*grade_name).unwrap();
-            output_line.push_str(&format!("\t{}", grade_count));
+        for grade in grade_columns.iter() {
+            output_line.push_str(&format!("\t{}", course_info.grade[*grade]));
         }
 
         csv_output_file
             .write_all(output_line.as_bytes())
-            .expect(&format!("Failed to write output line: {}", output_line));
+            .map_err(|err| {
+                format!(
+                    "Failed to write output line to file {}: {}",
+                    output_file, err
+                )
+            })?;
     }
 
     Ok(())
@@ -216,26 +372,505 @@ pub fn parse_csv_file(
 ///
 /// * `input_directory` - The path to the input directory.
 /// * `output_directory` - The path to the output directory.
+/// * `grade_view` - The grade view of every file, or `None` to read the view of each file from
+///   the manifest of the input directory, defaulting to the expanded view.
+/// * `rollup` - How to sum the sections of a course, or `None` for one row per section.
+///
+/// # Returns
+///
+/// * `Err(ParseError::Directory)` - If the input directory can't be read, or the output
+///   directory or its manifest can't be written.
+/// * `Err(ParseError::Files)` - If some files failed to parse. The other files are still parsed
+///   and listed in the output manifest.
 ///
 /// # Example
 ///
 /// ```
-/// use parse::parse_csv_directory;
+/// use ut_grade_parser::parse::parse_csv_directory;
+///
+/// let directory = std::env::temp_dir().join(format!("ut_grade_parser_doc_parse_dir_{}", std::process::id()));
+/// let export = directory.join("export");
+/// let parsed = directory.join("parsed");
+/// std::fs::create_dir_all(&export)?;
+/// std::fs::write(
+///     export.join("grade_distributions_2022-2023.csv"),
+///     "Semester,Section,Department,Department Code,Course Number,Course Title,Course Full Title,Letter Grade,Count of letter grade\n\
+///      Fall 2022,12345,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,B+,3\n",
+/// )?;
 ///
-/// parse_csv_directory("input_directory", "output_directory");
+/// parse_csv_directory(export.to_str().unwrap(), parsed.to_str().unwrap(), None, None)?;
+///
+/// assert!(parsed.join("grade_distributions_2022-2023.csv").exists());
+/// # std::fs::remove_dir_all(&directory)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
-pub fn parse_csv_directory(input_directory: &str, output_directory: &str) {
+pub fn parse_csv_directory(
+    input_directory: &str,
+    output_directory: &str,
+    grade_view: Option<GradeView>,
+    rollup: Option<Rollup>,
+) -> Result<(), ParseError> {
+    let paths = std::fs::read_dir(input_directory).map_err(|err| {
+        ParseError::Directory(format!(
+            "Failed to read the input directory {}: {}",
+            input_directory, err
+        ))
+    })?;
+
     // Create the output directory if it doesn't exist
-    std::fs::create_dir_all(output_directory).unwrap();
+    std::fs::create_dir_all(output_directory).map_err(|err| {
+        ParseError::Directory(format!(
+            "Failed to create the output directory {}: {}",
+            output_directory, err
+        ))
+    })?;
 
-    let paths = std::fs::read_dir(input_directory).unwrap();
+    let input_manifest: Option<Manifest> = match Manifest::load(input_directory) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            eprintln!(
+                "Failed to read the manifest of {}: {}",
+                input_directory, err
+            );
+            None
+        }
+    };
+    let mut output_manifest: Option<Manifest> = input_manifest.as_ref().map(|manifest| Manifest {
+        source: manifest.source.clone(),
+        tool_version: TOOL_VERSION.to_string(),
+        files: Vec::new(),
+    });
+    let mut errors: Vec<FileError> = Vec::new();
 
     for path in paths {
-        let path = path.unwrap().path();
-        let file_name = path.file_name().unwrap().to_str().unwrap();
+        let path = path
+            .map_err(|err| {
+                ParseError::Directory(format!(
+                    "Failed to read the input directory {}: {}",
+                    input_directory, err
+                ))
+            })?
+            .path();
+        if path.extension().is_none_or(|extension| extension != "csv") {
+            continue;
+        }
+        let (Some(file_name), Some(input_file)) = (
+            path.file_name().and_then(|file_name| file_name.to_str()),
+            path.to_str(),
+        ) else {
+            errors.push(FileError {
+                path: path.display().to_string(),
+                error: format!("{}: The file name isn't valid UTF-8", path.display()).into(),
+            });
+            continue;
+        };
         let output_file = format!("{}/{}", output_directory, file_name);
-        if let Err(err) = parse_csv_file(path.to_str().unwrap(), &output_file) {
-            eprintln!("Failed to parse CSV file: {}", err);
+        let source: Option<&ManifestEntry> = input_manifest
+            .as_ref()
+            .and_then(|manifest| manifest.entry(file_name));
+        if input_manifest.is_some() && source.is_none() {
+            eprintln!(
+                "{} is missing from the manifest of {}",
+                file_name, input_directory
+            );
+        }
+        let file_grade_view: GradeView = match (grade_view, source) {
+            (Some(grade_view), _) => grade_view,
+            (None, Some(source)) => match source.parameters.get(GradeView::PARAMETER) {
+                Some(value) => match value.parse() {
+                    Ok(grade_view) => grade_view,
+                    Err(err) => {
+                        errors.push(FileError {
+                            path: input_file.to_string(),
+                            error: format!("{}: {}", input_file, err).into(),
+                        });
+                        continue;
+                    }
+                },
+                None => GradeView::default(),
+            },
+            (None, None) => GradeView::default(),
+        };
+        if let Err(error) = parse_csv_file(input_file, &output_file, file_grade_view, rollup) {
+            errors.push(FileError {
+                path: input_file.to_string(),
+                error,
+            });
+            continue;
+        }
+
+        let (Some(source), Some(output_manifest)) = (source, &mut output_manifest) else {
+            continue;
+        };
+        match FileDigest::of_csv_file(std::path::Path::new(&output_file), b'\t') {
+            Ok(digest) => output_manifest.upsert(parsed_manifest_entry(source, digest)),
+            Err(err) => errors.push(FileError {
+                path: input_file.to_string(),
+                error: format!("Failed to read {}: {}", output_file, err).into(),
+            }),
+        }
+    }
+
+    if let Some(output_manifest) = output_manifest {
+        output_manifest.save(output_directory).map_err(|err| {
+            ParseError::Directory(format!(
+                "Failed to write the manifest of {}: {}",
+                output_directory, err
+            ))
+        })?;
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ParseError::Files(errors))
+    }
+}
+
+/// Describes a parsed file derived from an exported file.
+///
+/// # Arguments
+///
+/// * `source` - The manifest entry of the exported file.
+/// * `digest` - The digest of the parsed file.
+fn parsed_manifest_entry(source: &ManifestEntry, digest: FileDigest) -> ManifestEntry {
+    ManifestEntry {
+        file_name: source.file_name.clone(),
+        academic_year: source.academic_year.clone(),
+        filters: source.filters.clone(),
+        parameters: source.parameters.clone(),
+        fetched_at: source.fetched_at.clone(),
+        bytes: digest.bytes,
+        sha256: digest.sha256,
+        rows: digest.rows,
+        tool_version: TOOL_VERSION.to_string(),
+        derived_from: Some(Box::new(source.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses an export and returns the header and rows of the parsed file.
+    fn parse(
+        name: &str,
+        export: &str,
+        grade_view: GradeView,
+        rollup: Option<Rollup>,
+    ) -> (String, Vec<String>) {
+        let directory: std::path::PathBuf = std::env::temp_dir().join(format!(
+            "ut_grade_parser_parse_{}_{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory).unwrap();
+        let input_file: std::path::PathBuf = directory.join("export.csv");
+        let output_file: std::path::PathBuf = directory.join("parsed.csv");
+        std::fs::write(&input_file, export).unwrap();
+
+        parse_csv_file(
+            input_file.to_str().unwrap(),
+            output_file.to_str().unwrap(),
+            grade_view,
+            rollup,
+        )
+        .unwrap();
+        let parsed: String = std::fs::read_to_string(&output_file).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let mut lines: Vec<String> = parsed.lines().map(str::to_string).collect();
+        let header: String = lines.remove(0);
+        lines.sort();
+        (header, lines)
+    }
+
+    const EXPORT_HEADER: &str = "Semester,Section,Department,Department Code,Course Number,\
+                                 Course Title,Course Full Title,Letter Grade,Count of letter grade\n";
+
+    #[test]
+    fn parses_the_grade_columns_of_the_export() {
+        let (header, rows) = parse(
+            "expanded",
+            &format!(
+                "{}Fall 2022,12345,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,A+,3\n\
+                 Fall 2022,12345,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,B-,\"1,024\"\n",
+                EXPORT_HEADER
+            ),
+            GradeView::Expanded,
+            None,
+        );
+        assert_eq!(
+            header,
+            format!(
+                "{}\t{}",
+                CSV_HEADER,
+                Grade::ALL.map(Grade::label).join("\t")
+            )
+        );
+        assert_eq!(
+            rows,
+            vec![
+                "Fall 2022\t20229\t12345\tComputer Science\tC S\t314\tDATA STRUCTURES\t\
+                  C S 314 DATA STRUCTURES\t3\t0\t0\t0\t1024\t0\t0\t0\t0\t0\t0\t0\t0"
+            ]
+        );
+
+        let (header, rows) = parse(
+            "condensed",
+            &format!(
+                "{}Fall 2022,12345,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,Other,2\n\
+                 Fall 2022,12345,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,B,12\n\
+                 Spring 2023,54321,Mathematics,M,408C,CALCULUS,M 408C CALCULUS,A,7\n",
+                EXPORT_HEADER
+            ),
+            GradeView::Condensed,
+            None,
+        );
+        assert_eq!(
+            header,
+            format!(
+                "{}\tA (all)\tB (all)\tC (all)\tD (all)\tF\tOther",
+                CSV_HEADER
+            )
+        );
+        assert_eq!(
+            rows,
+            vec![
+                "Fall 2022\t20229\t12345\tComputer Science\tC S\t314\tDATA STRUCTURES\t\
+                 C S 314 DATA STRUCTURES\t0\t12\t0\t0\t0\t2",
+                "Spring 2023\t20232\t54321\tMathematics\tM\t408C\tCALCULUS\tM 408C CALCULUS\t7\t0\t0\t0\t0\t0",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_sections_and_rolls_them_up() {
+        let export: String = format!(
+            "{}Fall 2022,11111,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,A,10\n\
+             Fall 2022,11111,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,A+,1\n\
+             Fall 2022,22222,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,A,20\n\
+             Fall 2022,22222,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,B,5\n\
+             Spring 2023,33333,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,A,40\n",
+            EXPORT_HEADER
+        );
+        let course: &str = "Computer Science\tC S\t314\tDATA STRUCTURES\tC S 314 DATA STRUCTURES";
+
+        let (_, rows) = parse("sections", &export, GradeView::Condensed, None);
+        assert_eq!(
+            rows,
+            vec![
+                format!("Fall 2022\t20229\t11111\t{}\t11\t0\t0\t0\t0\t0", course),
+                format!("Fall 2022\t20229\t22222\t{}\t20\t5\t0\t0\t0\t0", course),
+                format!("Spring 2023\t20232\t33333\t{}\t40\t0\t0\t0\t0\t0", course),
+            ]
+        );
+
+        let (_, rows) = parse(
+            "semester_rollup",
+            &export,
+            GradeView::Condensed,
+            Some(Rollup::Semester),
+        );
+        assert_eq!(
+            rows,
+            vec![
+                format!("Fall 2022\t20229\t\t{}\t31\t5\t0\t0\t0\t0", course),
+                format!("Spring 2023\t20232\t\t{}\t40\t0\t0\t0\t0\t0", course),
+            ]
+        );
+
+        let (_, rows) = parse(
+            "course_rollup",
+            &export,
+            GradeView::Condensed,
+            Some(Rollup::Course),
+        );
+        assert_eq!(rows, vec![format!("\t\t\t{}\t71\t5\t0\t0\t0\t0", course)]);
+    }
+
+    #[test]
+    fn reports_malformed_rows() {
+        let directory: std::path::PathBuf = std::env::temp_dir().join(format!(
+            "ut_grade_parser_parse_malformed_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory).unwrap();
+        let input_file: std::path::PathBuf = directory.join("export.csv");
+        let output_file: std::path::PathBuf = directory.join("parsed.csv");
+        let row: &str = "Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES";
+
+        for (export_row, message) in [
+            (
+                format!("Fall 2022,12345,{},A,many", row),
+                "row 2: Invalid count of letter grade \"many\"",
+            ),
+            (
+                format!("Fall 2022,,{},A,3", row),
+                "row 2: Invalid section \"\"",
+            ),
+            (
+                format!("Fall 2022,12345,{},E,3", row),
+                "row 2: Unknown grade \"E\"",
+            ),
+            (
+                format!("Fall 22,12345,{},A,3", row),
+                "row 2: Invalid term \"Fall 22\"",
+            ),
+            (
+                format!("Fall 2022,12345,{},A-,3", row),
+                "row 2: The grade A- isn't a column of the Condensed grade view",
+            ),
+        ] {
+            std::fs::write(
+                &input_file,
+                format!(
+                    "{}Fall 2022,12345,{},B,1\n{}\n",
+                    EXPORT_HEADER, row, export_row
+                ),
+            )
+            .unwrap();
+            let err: Box<dyn std::error::Error> = parse_csv_file(
+                input_file.to_str().unwrap(),
+                output_file.to_str().unwrap(),
+                GradeView::Condensed,
+                None,
+            )
+            .unwrap_err();
+            assert!(err.to_string().contains(message), "{}", err);
+        }
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn reads_the_grade_view_of_each_file_from_the_manifest() {
+        let directory: std::path::PathBuf = std::env::temp_dir().join(format!(
+            "ut_grade_parser_parse_manifest_view_{}",
+            std::process::id()
+        ));
+        let export: std::path::PathBuf = directory.join("export");
+        let parsed: std::path::PathBuf = directory.join("parsed");
+        std::fs::create_dir_all(&export).unwrap();
+        let entry = |file_name: &str, grade_view: GradeView| ManifestEntry {
+            file_name: file_name.to_string(),
+            academic_year: "2022-2023".to_string(),
+            filters: Default::default(),
+            parameters: [(GradeView::PARAMETER.to_string(), grade_view.to_string())].into(),
+            fetched_at: String::new(),
+            bytes: 0,
+            sha256: String::new(),
+            rows: 1,
+            tool_version: TOOL_VERSION.to_string(),
+            derived_from: None,
+        };
+        let row: &str =
+            "Fall 2022,12345,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES,A,3";
+        for file_name in ["expanded.csv", "condensed.csv"] {
+            std::fs::write(
+                export.join(file_name),
+                format!("{}{}\n", EXPORT_HEADER, row),
+            )
+            .unwrap();
         }
+        Manifest {
+            source: String::new(),
+            tool_version: TOOL_VERSION.to_string(),
+            files: vec![
+                entry("expanded.csv", GradeView::Expanded),
+                entry("condensed.csv", GradeView::Condensed),
+            ],
+        }
+        .save(export.to_str().unwrap())
+        .unwrap();
+
+        parse_csv_directory(
+            export.to_str().unwrap(),
+            parsed.to_str().unwrap(),
+            None,
+            None,
+        )
+        .unwrap();
+
+        let header = |file_name: &str| -> String {
+            std::fs::read_to_string(parsed.join(file_name))
+                .unwrap()
+                .lines()
+                .next()
+                .unwrap()
+                .to_string()
+        };
+        assert!(
+            header("expanded.csv").ends_with("\tA\tA-\tB+\tB\tB-\tC+\tC\tC-\tD+\tD\tD-\tF\tOther")
+        );
+        assert!(header("condensed.csv").ends_with("\tA (all)\tB (all)\tC (all)\tD (all)\tF\tOther"));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn reports_a_missing_input_directory() {
+        let directory: std::path::PathBuf = std::env::temp_dir().join(format!(
+            "ut_grade_parser_parse_missing_{}",
+            std::process::id()
+        ));
+
+        let err: ParseError = parse_csv_directory(
+            directory.join("export").to_str().unwrap(),
+            directory.join("parsed").to_str().unwrap(),
+            None,
+            None,
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&err, ParseError::Directory(message)
+                if message.starts_with("Failed to read the input directory")),
+            "{}",
+            err
+        );
+        assert!(!directory.exists());
+    }
+
+    #[test]
+    fn collects_the_errors_of_each_file() {
+        let directory: std::path::PathBuf = std::env::temp_dir().join(format!(
+            "ut_grade_parser_parse_file_errors_{}",
+            std::process::id()
+        ));
+        let export: std::path::PathBuf = directory.join("export");
+        let parsed: std::path::PathBuf = directory.join("parsed");
+        std::fs::create_dir_all(&export).unwrap();
+        let row: &str =
+            "Fall 2022,12345,Computer Science,C S,314,DATA STRUCTURES,C S 314 DATA STRUCTURES";
+        std::fs::write(
+            export.join("good.csv"),
+            format!("{}{},A,3\n", EXPORT_HEADER, row),
+        )
+        .unwrap();
+        std::fs::write(
+            export.join("bad.csv"),
+            format!("{}{},E,3\n", EXPORT_HEADER, row),
+        )
+        .unwrap();
+
+        let err: ParseError = parse_csv_directory(
+            export.to_str().unwrap(),
+            parsed.to_str().unwrap(),
+            None,
+            None,
+        )
+        .unwrap_err();
+
+        let ParseError::Files(errors) = &err else {
+            panic!("Unexpected error {}", err);
+        };
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].path.ends_with("bad.csv"));
+        assert!(
+            err.to_string()
+                .contains("bad.csv, row 1: Unknown grade \"E\""),
+            "{}",
+            err
+        );
+        assert!(parsed.join("good.csv").exists());
+        std::fs::remove_dir_all(&directory).unwrap();
     }
 }
@@ -0,0 +1,172 @@
+//! This module defines the terms (semesters) of the grade distributions.
+//!
+//! The main types in this module are:
+//! - `Season`: The season of a term, in chronological order within a calendar year.
+//! - `Term`: A semester, parsed from labels like `Fall 2022` and convertible to and from UT's
+//!   numeric `CCYYS` term codes, e.g. `20229`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// The season of a term. Seasons sort chronologically within a calendar year.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Season {
+    Spring,
+    Summer,
+    Fall,
+}
+
+impl Season {
+    /// Every season, in chronological order
+    pub const ALL: [Season; 3] = [Season::Spring, Season::Summer, Season::Fall];
+
+    /// The label of the season in the exports, e.g. `Fall`.
+    pub fn label(self) -> &'static str {
+        match self {
+            Season::Spring => "Spring",
+            Season::Summer => "Summer",
+            Season::Fall => "Fall",
+        }
+    }
+
+    /// The last digit of the term codes of the season.
+    fn code(self) -> u32 {
+        match self {
+            Season::Spring => 2,
+            Season::Summer => 6,
+            Season::Fall => 9,
+        }
+    }
+}
+
+/// A term (semester), e.g. Fall 2022. Terms sort chronologically.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Term {
+    /// The calendar year of the term
+    pub year: u16,
+    pub season: Season,
+}
+
+/// A semester label or term code that isn't a valid term.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidTerm(pub String);
+
+impl fmt::Display for InvalidTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid term {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidTerm {}
+
+impl Term {
+    /// The UT term code of the term: the year followed by a digit for the season, e.g. `20229`
+    /// for Fall 2022, `20232` for Spring 2023 and `20236` for Summer 2023.
+    pub fn code(self) -> u32 {
+        u32::from(self.year) * 10 + self.season.code()
+    }
+
+    /// Reads a UT term code, e.g. `20229` for Fall 2022.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Term)` - The term of the code.
+    /// * `Err(InvalidTerm)` - If the code doesn't have a four-digit year and a season digit.
+    pub fn from_code(code: u32) -> Result<Self, InvalidTerm> {
+        let invalid = || InvalidTerm(code.to_string());
+        let season: Season = Season::ALL
+            .into_iter()
+            .find(|season| season.code() == code % 10)
+            .ok_or_else(invalid)?;
+        let year: u16 = u16::try_from(code / 10)
+            .ok()
+            .filter(|year| (1000..=9999).contains(year))
+            .ok_or_else(invalid)?;
+
+        Ok(Term { year, season })
+    }
+
+    /// The academic year of the term, identified by the calendar year it starts in like
+    /// `AcademicYearRange`: an academic year starts in the fall and ends after the summer, so
+    /// Fall 2022, Spring 2023 and Summer 2023 belong to `2022` (2022-2023).
+    pub fn academic_year(self) -> u16 {
+        match self.season {
+            Season::Fall => self.year,
+            Season::Spring | Season::Summer => self.year - 1,
+        }
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.season.label(), self.year)
+    }
+}
+
+impl FromStr for Term {
+    type Err = InvalidTerm;
+
+    /// Parses a semester label of the exports, e.g. `Fall 2022`.
+    fn from_str(label: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidTerm(label.trim().to_string());
+        let (season, year) = label.trim().split_once(' ').ok_or_else(invalid)?;
+        let season: Season = Season::ALL
+            .into_iter()
+            .find(|candidate| candidate.label().eq_ignore_ascii_case(season.trim()))
+            .ok_or_else(invalid)?;
+        let year: u16 = year
+            .trim()
+            .parse()
+            .ok()
+            .filter(|year| (1000..=9999).contains(year))
+            .ok_or_else(invalid)?;
+
+        Ok(Term { year, season })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_labels_and_term_codes() {
+        let fall: Term = "Fall 2022".parse().unwrap();
+        assert_eq!(
+            fall,
+            Term {
+                year: 2022,
+                season: Season::Fall
+            }
+        );
+        assert_eq!(fall.to_string(), "Fall 2022");
+        assert_eq!(fall.code(), 20229);
+        assert_eq!(Term::from_code(20229), Ok(fall));
+        assert_eq!(Term::from_code(20236).unwrap().to_string(), "Summer 2023");
+        assert!(Term::from_code(20225).is_err());
+        assert!(Term::from_code(99).is_err());
+        assert!("Winter 2022".parse::<Term>().is_err());
+        assert!("Fall".parse::<Term>().is_err());
+    }
+
+    #[test]
+    fn orders_terms_chronologically() {
+        let mut terms: Vec<Term> = ["Fall 2022", "Summer 2023", "Spring 2023", "Fall 2021"]
+            .iter()
+            .map(|label| label.parse().unwrap())
+            .collect();
+        terms.sort();
+        assert_eq!(
+            terms.iter().map(Term::to_string).collect::<Vec<String>>(),
+            ["Fall 2021", "Fall 2022", "Spring 2023", "Summer 2023"]
+        );
+        assert_eq!(
+            terms
+                .iter()
+                .map(|term| term.academic_year())
+                .collect::<Vec<u16>>(),
+            [2021, 2022, 2022, 2022]
+        );
+    }
+}